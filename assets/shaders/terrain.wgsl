@@ -15,12 +15,94 @@
 }
 #endif
 
+struct TerrainMaterial {
+    sand_color: vec4<f32>,
+    grass_color: vec4<f32>,
+    rock_color: vec4<f32>,
+    snow_color: vec4<f32>,
+    sea_level: f32,
+    beach_height: f32,
+    snow_line: f32,
+    world_height: f32,
+    rock_slope_start: f32,
+    rock_slope_end: f32,
+    overlay_mode: u32,
+    contour_interval: f32,
+    cell_count: f32,
+}
+
+@group(2) @binding(100)
+var<uniform> terrain_material: TerrainMaterial;
+@group(2) @binding(101)
+var overlay_texture: texture_2d<f32>;
+@group(2) @binding(102)
+var overlay_sampler: sampler;
+
+// Matches `OverlayMode::shader_index`
+const OVERLAY_CONTOURS: u32 = 1u;
+const OVERLAY_GRID: u32 = 2u;
+const OVERLAY_POLITICAL: u32 = 3u;
+const OVERLAY_LINE_COLOR: vec4<f32> = vec4<f32>(0.05, 0.05, 0.05, 1.0);
+
+// Kept in sync with `TerrainMaterial::color_at`
+fn terrain_color(height: f32, normal: vec3<f32>) -> vec4<f32> {
+    let slope = acos(clamp(normal.y, -1.0, 1.0));
+    let blend = 0.05 * terrain_material.world_height;
+    let beach = terrain_material.sea_level + terrain_material.beach_height;
+    var color = mix(
+        terrain_material.sand_color,
+        terrain_material.grass_color,
+        smoothstep(beach - blend, beach + blend, height),
+    );
+    color = mix(
+        color,
+        terrain_material.rock_color,
+        smoothstep(terrain_material.rock_slope_start, terrain_material.rock_slope_end, slope),
+    );
+    color = mix(
+        color,
+        terrain_material.snow_color,
+        smoothstep(terrain_material.snow_line - blend, terrain_material.snow_line + blend, height),
+    );
+    return color;
+}
+
+// Strength of an anti-aliased line drawn wherever `value` crosses a whole number
+fn line_strength(value: f32) -> f32 {
+    let distance = abs(fract(value - 0.5) - 0.5);
+    return 1.0 - smoothstep(0.0, fwidth(value) * 1.5, distance);
+}
+
+fn apply_overlay(color: vec4<f32>, world_position: vec3<f32>) -> vec4<f32> {
+    switch terrain_material.overlay_mode {
+        case OVERLAY_CONTOURS: {
+            let line = line_strength(world_position.y / terrain_material.contour_interval);
+            return mix(color, OVERLAY_LINE_COLOR, line * 0.7);
+        }
+        case OVERLAY_GRID: {
+            // Heightmap vertices sit on whole world coordinates, so cell edges do too
+            let line = max(line_strength(world_position.x), line_strength(world_position.z));
+            return mix(color, OVERLAY_LINE_COLOR, line * 0.5);
+        }
+        case OVERLAY_POLITICAL: {
+            let uv = world_position.xz / terrain_material.cell_count;
+            let data = textureSample(overlay_texture, overlay_sampler, uv);
+            return vec4<f32>(mix(color.rgb, data.rgb, data.a), color.a);
+        }
+        default: {
+            return color;
+        }
+    }
+}
+
 @fragment
 fn fragment(
     in: VertexOutput,
     @builtin(front_facing) is_front: bool,
 ) -> FragmentOutput {
     var pbr_input = pbr_input_from_standard_material(in, is_front);
+    pbr_input.material.base_color = terrain_color(in.world_position.y, pbr_input.world_normal);
+    pbr_input.material.base_color = apply_overlay(pbr_input.material.base_color, in.world_position.xyz);
     pbr_input.material.base_color = alpha_discard(pbr_input.material, pbr_input.material.base_color);
 
 #ifdef PREPASS_PIPELINE
@@ -29,7 +111,6 @@ fn fragment(
     var out: FragmentOutput;
     out.color = apply_pbr_lighting(pbr_input);
     out.color = main_pass_post_lighting_processing(pbr_input, out.color);
-    out.color = out.color * f32(2.0);
 #endif
 
     return out;
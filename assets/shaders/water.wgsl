@@ -0,0 +1,65 @@
+#import bevy_pbr::{
+    pbr_fragment::pbr_input_from_standard_material,
+    pbr_functions::alpha_discard,
+    mesh_view_bindings::globals,
+}
+
+#ifdef PREPASS_PIPELINE
+#import bevy_pbr::{
+    prepass_io::{VertexOutput, FragmentOutput},
+    pbr_deferred_functions::deferred_output,
+}
+#else
+#import bevy_pbr::{
+    forward_io::{VertexOutput, FragmentOutput},
+    pbr_functions::{apply_pbr_lighting, main_pass_post_lighting_processing},
+}
+#endif
+
+struct WaterMaterial {
+    shallow_color: vec4<f32>,
+    deep_color: vec4<f32>,
+    max_depth: f32,
+    wave_speed: f32,
+    wave_scale: f32,
+}
+
+@group(2) @binding(100)
+var<uniform> water_material: WaterMaterial;
+
+@fragment
+fn fragment(
+    in: VertexOutput,
+    @builtin(front_facing) is_front: bool,
+) -> FragmentOutput {
+    var pbr_input = pbr_input_from_standard_material(in, is_front);
+
+    // The first uv channel holds how far the terrain lies below the water surface
+#ifdef VERTEX_UVS_A
+    let depth = clamp(in.uv.x / water_material.max_depth, 0.0, 1.0);
+#else
+    let depth = 1.0;
+#endif
+    pbr_input.material.base_color = mix(water_material.shallow_color, water_material.deep_color, depth);
+
+    // Ripple the normal with a few crossing sine waves
+    let t = globals.time * water_material.wave_speed;
+    let p = in.world_position.xz * water_material.wave_scale;
+    let ripple = vec2<f32>(
+        sin(p.x + t) + sin(p.x * 1.7 - p.y * 0.9 + t * 1.3),
+        cos(p.y + t * 0.8) + cos(p.y * 1.3 + p.x * 1.1 - t * 1.1),
+    ) * 0.05;
+    pbr_input.N = normalize(vec3<f32>(ripple.x, 1.0, ripple.y));
+
+    pbr_input.material.base_color = alpha_discard(pbr_input.material, pbr_input.material.base_color);
+
+#ifdef PREPASS_PIPELINE
+    let out = deferred_output(in, pbr_input);
+#else
+    var out: FragmentOutput;
+    out.color = apply_pbr_lighting(pbr_input);
+    out.color = main_pass_post_lighting_processing(pbr_input, out.color);
+#endif
+
+    return out;
+}
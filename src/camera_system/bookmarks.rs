@@ -0,0 +1,432 @@
+use std::collections::BTreeMap;
+use std::f32::consts::PI;
+use std::path::PathBuf;
+
+use bevy::{prelude::*, window::PrimaryWindow};
+use serde::{Deserialize, Serialize};
+
+use crate::camera_system::{
+    self, zoom_mouse, CameraTarget, StrategyCamera, ThirdPersonCamera, ThirdPersonCameraTarget,
+};
+use crate::config_parser::{load_yaml, save_yaml, user_save_path, MapConfig};
+use crate::controls::{ActionState, InputAction};
+use crate::loading_screen::AppState::InGame;
+
+// Flights take longer over longer distances, within these bounds in seconds
+const FLIGHT_TIME_MIN: f32 = 0.6;
+const FLIGHT_TIME_MAX: f32 = 2.5;
+// World units covered per second on top of the minimum flight time
+const FLIGHT_SPEED: f32 = 150.0;
+
+pub struct CameraBookmarkPlugin;
+
+impl Plugin for CameraBookmarkPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraBookmarks>()
+            .add_event::<FocusCameraEvent>()
+            .add_systems(OnEnter(InGame), load_camera_bookmarks)
+            .add_systems(
+                Update,
+                (
+                    (store_and_recall_bookmarks, focus_cameras, fly_to_bookmark)
+                        .chain()
+                        .after(zoom_mouse)
+                        .before(super::place_strategy_camera)
+                        .after(super::strategy::rotate_strategy_camera)
+                        .run_if(in_state(InGame)),
+                    save_camera_bookmarks.run_if(in_state(InGame)),
+                ),
+            );
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CameraBookmark {
+    pub focus: Vec3,
+    pub rotation: Quat,
+    pub zoom: f32,
+}
+
+// The store bookmark actions, Ctrl+1..9 by default, keep the current view and the recall
+// actions, 1..9, fly back to it. The slots are kept in the saves folder, in a file for each
+// world, so they survive between sessions.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, Resource)]
+#[serde(transparent)]
+pub struct CameraBookmarks {
+    slots: BTreeMap<u8, CameraBookmark>,
+}
+
+impl CameraBookmarks {
+    pub fn load(map_config: &MapConfig) -> CameraBookmarks {
+        return load_yaml(bookmarks_path(map_config), "camera bookmarks").unwrap_or_default();
+    }
+
+    pub fn save(&self, map_config: &MapConfig) {
+        save_yaml(bookmarks_path(map_config), "camera bookmarks", self);
+    }
+
+    pub fn get(&self, slot: u8) -> Option<&CameraBookmark> {
+        return self.slots.get(&slot);
+    }
+
+    pub fn set(&mut self, slot: u8, bookmark: CameraBookmark) {
+        self.slots.insert(slot, bookmark);
+    }
+}
+
+// Flies a camera over to look at a point, keeping its rotation and zoom. A third person camera
+// switches to the strategy view first, since it cannot leave the player.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct FocusCameraEvent {
+    pub camera: Entity,
+    pub point: Vec3,
+}
+
+// Eased move between two views. While it is on a camera the flight places it, and the orbit,
+// follow and strategy systems leave it be. The camera lands in the mode it took off in.
+#[derive(Component)]
+pub struct CameraFlight {
+    from: CameraBookmark,
+    to: CameraBookmark,
+    elapsed: f32,
+    duration: f32,
+}
+
+pub(super) fn current_view(cam: &ThirdPersonCamera, transform: &Transform) -> CameraBookmark {
+    // The pivot the camera orbits, before any pull in for terrain
+    let direction = transform.rotation * Vec3::Z;
+    return CameraBookmark {
+        focus: transform.translation - direction * cam.collision_radius,
+        rotation: transform.rotation,
+        zoom: cam.zoom.target_radius,
+    };
+}
+
+// Only the camera taking input stores and recalls views, so picture in picture or split screen
+// cameras keep their own
+fn store_and_recall_bookmarks(
+    mut commands: Commands,
+    actions: Res<ActionState>,
+    mut bookmarks: ResMut<CameraBookmarks>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    cam_q: Query<(
+        Entity,
+        &Camera,
+        &ThirdPersonCamera,
+        &Transform,
+        Has<StrategyCamera>,
+    )>,
+) {
+    let slot_for = |slot_actions: [InputAction; 9]| {
+        return slot_actions
+            .iter()
+            .position(|action| actions.just_pressed(*action))
+            .map(|slot| slot as u8 + 1);
+    };
+    let stored = slot_for(InputAction::STORE_BOOKMARK);
+    let recalled = slot_for(InputAction::RECALL_BOOKMARK);
+    if stored.is_none() && recalled.is_none() {
+        return;
+    }
+    let input_camera = camera_system::input_camera(
+        window_q.get_single().ok(),
+        cam_q
+            .iter()
+            .map(|(entity, camera, _, _, _)| (entity, camera)),
+    );
+    let Some((entity, _, cam, transform, is_strategy)) =
+        input_camera.and_then(|entity| cam_q.get(entity).ok())
+    else {
+        return;
+    };
+
+    if let Some(slot) = stored {
+        bookmarks.set(slot, current_view(cam, transform));
+        return;
+    }
+    let Some(bookmark) = recalled.and_then(|slot| bookmarks.get(slot)) else {
+        return;
+    };
+    start_flight(
+        &mut commands,
+        entity,
+        cam,
+        transform,
+        is_strategy,
+        *bookmark,
+    );
+}
+
+fn focus_cameras(
+    mut commands: Commands,
+    mut focus_evr: EventReader<FocusCameraEvent>,
+    mut cam_q: Query<(&mut ThirdPersonCamera, &Transform, Has<StrategyCamera>)>,
+) {
+    for FocusCameraEvent { camera, point } in focus_evr.read().copied() {
+        let Ok((mut cam, transform, is_strategy)) = cam_q.get_mut(camera) else {
+            continue;
+        };
+        let view = current_view(&cam, transform);
+        if !is_strategy {
+            cam.focus = view.focus;
+            commands
+                .entity(camera)
+                .insert(StrategyCamera::facing(transform.rotation));
+        }
+        let destination = CameraBookmark {
+            focus: point,
+            ..view
+        };
+        start_flight(&mut commands, camera, &cam, transform, true, destination);
+    }
+}
+
+fn start_flight(
+    commands: &mut Commands,
+    entity: Entity,
+    cam: &ThirdPersonCamera,
+    transform: &Transform,
+    is_strategy: bool,
+    destination: CameraBookmark,
+) {
+    let view = current_view(cam, transform);
+    // The third person view always stays on its target, so there the flight only turns and
+    // zooms to the stored view
+    let destination = if is_strategy {
+        destination
+    } else {
+        CameraBookmark {
+            focus: view.focus,
+            ..destination
+        }
+    };
+    let distance = view.focus.distance(destination.focus);
+    commands.entity(entity).insert(CameraFlight {
+        from: view,
+        to: destination,
+        elapsed: 0.0,
+        duration: (FLIGHT_TIME_MIN + distance / FLIGHT_SPEED).min(FLIGHT_TIME_MAX),
+    });
+}
+
+fn fly_to_bookmark(
+    mut commands: Commands,
+    time: Res<Time>,
+    target_q: Query<&Transform, With<ThirdPersonCameraTarget>>,
+    mut cam_q: Query<
+        (
+            Entity,
+            &mut ThirdPersonCamera,
+            Option<&mut StrategyCamera>,
+            Option<&CameraTarget>,
+            &mut CameraFlight,
+            &mut Transform,
+        ),
+        Without<ThirdPersonCameraTarget>,
+    >,
+) {
+    for (entity, mut cam, strategy, target, mut flight, mut transform) in cam_q.iter_mut() {
+        flight.elapsed += time.delta_seconds();
+        let progress = (flight.elapsed / flight.duration).clamp(0.0, 1.0);
+        let t = ease_in_out(progress);
+        let (from, to) = (flight.from, flight.to);
+
+        // Rise out over long flights so the view passes over the land in between
+        let zoom = from.zoom.lerp(to.zoom, t);
+        let lift = from.focus.distance(to.focus) * 0.25 * (progress * PI).sin();
+        let zoom = zoom + lift.min((cam.zoom.max - zoom).max(0.0));
+
+        // A third person camera keeps up with its target if it moves during the flight
+        let followed = target.and_then(|target| target_q.get(target.0).ok());
+        cam.focus = match (&strategy, followed) {
+            (None, Some(followed)) => followed.translation,
+            _ => from.focus.lerp(to.focus, t),
+        };
+        cam.zoom.radius = zoom;
+        cam.zoom.target_radius = zoom;
+        cam.target_rotation = from.rotation.slerp(to.rotation, t);
+        transform.rotation = cam.target_rotation;
+        transform.translation = cam.focus + transform.rotation * Vec3::new(0.0, 0.0, zoom);
+
+        if let Some(mut strategy) = strategy {
+            strategy.pan_velocity = Vec2::ZERO;
+            if progress >= 1.0 {
+                strategy.look_along(to.rotation, &cam);
+            }
+        }
+        if progress >= 1.0 {
+            commands.entity(entity).remove::<CameraFlight>();
+        }
+    }
+}
+
+fn ease_in_out(t: f32) -> f32 {
+    return t * t * (3.0 - 2.0 * t);
+}
+
+fn bookmarks_path(map_config: &MapConfig) -> Option<PathBuf> {
+    return user_save_path(&format!("camera_bookmarks_{}.yml", map_config.world_name()));
+}
+
+fn load_camera_bookmarks(mut bookmarks: ResMut<CameraBookmarks>, map_config: Res<MapConfig>) {
+    // Loading is not a change to save back
+    *bookmarks.bypass_change_detection() = CameraBookmarks::load(&map_config);
+}
+
+fn save_camera_bookmarks(bookmarks: Res<CameraBookmarks>, map_config: Res<MapConfig>) {
+    if bookmarks.is_changed() && !bookmarks.is_added() {
+        bookmarks.save(&map_config);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::time::{TimePlugin, TimeUpdateStrategy};
+
+    use super::*;
+    use crate::camera_system::testing::{self, spawn_cameras, tap_keys};
+
+    fn flight_app() -> App {
+        let mut app = App::new();
+        app.add_plugins(TimePlugin)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+                1.0 / 60.0,
+            )))
+            .add_systems(Update, fly_to_bookmark);
+        return app;
+    }
+
+    fn input_app() -> App {
+        let mut app = testing::input_app();
+        app.init_resource::<CameraBookmarks>()
+            .add_event::<FocusCameraEvent>()
+            .add_systems(Update, (store_and_recall_bookmarks, focus_cameras));
+        return app;
+    }
+
+    fn bookmark() -> CameraBookmark {
+        return CameraBookmark {
+            focus: Vec3::new(40.0, 2.0, 25.0),
+            rotation: Quat::from_rotation_y(2.0) * Quat::from_rotation_x(-0.9),
+            zoom: 6.0,
+        };
+    }
+
+    // Runs the flight until it lands
+    fn land(app: &mut App, camera: Entity) {
+        for _ in 0..600 {
+            app.update();
+            if !app.world().entity(camera).contains::<CameraFlight>() {
+                return;
+            }
+        }
+        panic!("The flight never landed");
+    }
+
+    #[test]
+    fn strategy_flight_lands_on_the_stored_view() {
+        let mut app = flight_app();
+        let cam = ThirdPersonCamera::default();
+        let transform = Transform::from_xyz(5.0, 6.0, 9.0);
+        let camera = app
+            .world_mut()
+            .spawn((StrategyCamera::facing(transform.rotation), transform))
+            .id();
+        start_flight(
+            &mut app.world_mut().commands(),
+            camera,
+            &cam,
+            &transform,
+            true,
+            bookmark(),
+        );
+        app.world_mut().entity_mut(camera).insert(cam);
+        land(&mut app, camera);
+
+        let entity = app.world().entity(camera);
+        let cam = entity.get::<ThirdPersonCamera>().unwrap();
+        let transform = entity.get::<Transform>().unwrap();
+        let strategy = entity.get::<StrategyCamera>().unwrap();
+        assert!(cam.focus.distance(bookmark().focus) < 1e-4);
+        assert!((cam.zoom.target_radius - bookmark().zoom).abs() < 1e-4);
+        assert!(transform.rotation.angle_between(bookmark().rotation) < 1e-3);
+        let mut expected = StrategyCamera::default();
+        expected.look_along(bookmark().rotation, cam);
+        assert!((strategy.yaw - expected.yaw).abs() < 1e-4);
+        assert!((strategy.tilt - expected.tilt).abs() < 1e-4);
+    }
+
+    #[test]
+    fn third_person_flight_stays_on_the_target() {
+        let mut app = flight_app();
+        let target = app
+            .world_mut()
+            .spawn((ThirdPersonCameraTarget, Transform::from_xyz(10.0, 1.0, 5.0)))
+            .id();
+        let cam = ThirdPersonCamera::default();
+        let transform = Transform::from_xyz(10.0, 1.0, 5.0 + cam.zoom.max);
+        let camera = app
+            .world_mut()
+            .spawn((transform, CameraTarget(target)))
+            .id();
+        start_flight(
+            &mut app.world_mut().commands(),
+            camera,
+            &cam,
+            &transform,
+            false,
+            bookmark(),
+        );
+        app.world_mut().entity_mut(camera).insert(cam);
+        land(&mut app, camera);
+
+        let entity = app.world().entity(camera);
+        let cam = entity.get::<ThirdPersonCamera>().unwrap();
+        assert!(!entity.contains::<StrategyCamera>());
+        assert_eq!(cam.focus, Vec3::new(10.0, 1.0, 5.0));
+        assert!(cam.target_rotation.angle_between(bookmark().rotation) < 1e-3);
+        assert!((cam.zoom.target_radius - bookmark().zoom).abs() < 1e-4);
+    }
+
+    #[test]
+    fn bookmarks_store_and_recall_the_input_camera() {
+        let mut app = input_app();
+        let [back, front] = spawn_cameras(&mut app);
+        tap_keys(&mut app, &[KeyCode::ControlLeft, KeyCode::Digit1]);
+
+        let entity = app.world().entity(front);
+        let expected = current_view(
+            entity.get::<ThirdPersonCamera>().unwrap(),
+            entity.get::<Transform>().unwrap(),
+        );
+        assert_eq!(
+            app.world().resource::<CameraBookmarks>().get(1),
+            Some(&expected)
+        );
+
+        app.world_mut()
+            .resource_mut::<CameraBookmarks>()
+            .set(2, bookmark());
+        tap_keys(&mut app, &[KeyCode::Digit2]);
+        assert!(app.world().entity(front).contains::<CameraFlight>());
+        assert!(!app.world().entity(back).contains::<CameraFlight>());
+    }
+
+    #[test]
+    fn focus_flies_only_the_chosen_camera() {
+        let mut app = input_app();
+        let [back, front] = spawn_cameras(&mut app);
+        app.world_mut().send_event(FocusCameraEvent {
+            camera: front,
+            point: Vec3::new(30.0, 1.0, 30.0),
+        });
+        app.update();
+
+        let entity = app.world().entity(front);
+        assert!(entity.contains::<CameraFlight>());
+        assert!(entity.contains::<StrategyCamera>());
+        assert!(!app.world().entity(back).contains::<CameraFlight>());
+    }
+}
@@ -0,0 +1,63 @@
+use bevy::prelude::*;
+
+use crate::camera_system::ThirdPersonCamera;
+use crate::terrain_generator::TerrainQuery;
+
+// Distance between height samples along the line of sight. Heightmap cells are one world unit
+// wide, so this catches every ridge the camera could end up behind.
+const SIGHT_STEP: f32 = 0.25;
+
+// Runs after the camera modes have placed the camera at the full zoom distance. If the terrain
+// blocks the line of sight from the pivot the camera is pulled in straight away, and once the
+// view clears it eases back out to the zoom distance.
+pub fn keep_camera_above_terrain(
+    time: Res<Time>,
+    terrain: TerrainQuery,
+    mut cam_q: Query<(&mut ThirdPersonCamera, &mut Transform)>,
+) {
+    for (mut cam, mut transform) in cam_q.iter_mut() {
+        let direction = transform.rotation * Vec3::Z;
+        let pivot = transform.translation - direction * cam.zoom.radius;
+        let clear = clear_distance(
+            &terrain,
+            pivot,
+            direction,
+            cam.zoom.radius,
+            cam.ground_clearance,
+        );
+
+        if clear < cam.collision_radius {
+            cam.collision_radius = clear;
+        } else {
+            let t = 1.0 - (-cam.zoom_restore_speed * time.delta_seconds()).exp();
+            cam.collision_radius += (clear - cam.collision_radius) * t;
+        }
+        transform.translation = pivot + direction * cam.collision_radius;
+
+        if let Some(height) = terrain.height_at(transform.translation.x, transform.translation.z) {
+            transform.translation.y = transform.translation.y.max(height + cam.ground_clearance);
+        }
+    }
+}
+
+// How far from the pivot the camera can sit along `direction` while staying `clearance` above
+// the ground. Positions off the map are treated as clear.
+fn clear_distance(
+    terrain: &TerrainQuery,
+    pivot: Vec3,
+    direction: Vec3,
+    radius: f32,
+    clearance: f32,
+) -> f32 {
+    let steps = (radius / SIGHT_STEP).ceil() as usize;
+    for step in 1..=steps {
+        let distance = (step as f32 * SIGHT_STEP).min(radius);
+        let position = pivot + direction * distance;
+        if let Some(height) = terrain.height_at(position.x, position.z) {
+            if position.y < height + clearance {
+                return (distance - SIGHT_STEP).max(0.0);
+            }
+        }
+    }
+    return radius;
+}
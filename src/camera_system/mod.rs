@@ -1,19 +1,46 @@
+mod bookmarks;
+mod collision;
 mod mouse;
+mod path;
+mod strategy;
+#[cfg(test)]
+mod testing;
 
-use bevy::prelude::*;
-pub use mouse::{orbit_mouse, MousePlugin};
+use bevy::{prelude::*, window::PrimaryWindow};
+pub use bookmarks::FocusCameraEvent;
+pub use mouse::{orbit_mouse, zoom_mouse, MousePlugin};
+pub use path::CameraPathPlayback;
+pub use strategy::{place_strategy_camera, StrategyCamera, StrategyCameraPlugin};
 
+use crate::controls::ActionState;
 use crate::loading_screen;
 
 pub struct ThirdPersonCameraPlugin;
 
 impl Plugin for ThirdPersonCameraPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(MousePlugin).add_systems(
+        app.add_plugins((
+            MousePlugin,
+            StrategyCameraPlugin,
+            bookmarks::CameraBookmarkPlugin,
+            path::CameraPathPlugin,
+        ))
+        .init_resource::<LastInputCamera>()
+        .add_systems(
             Update,
-            (sync_player_camera
-                .after(orbit_mouse)
-                .run_if(in_state(loading_screen::AppState::InGame)),),
+            (
+                track_last_input_camera.run_if(in_state(loading_screen::AppState::InGame)),
+                link_camera_targets
+                    .before(sync_player_camera)
+                    .run_if(in_state(loading_screen::AppState::InGame)),
+                sync_player_camera
+                    .after(orbit_mouse)
+                    .run_if(in_state(loading_screen::AppState::InGame)),
+                collision::keep_camera_above_terrain
+                    .after(sync_player_camera)
+                    .after(place_strategy_camera)
+                    .run_if(in_state(loading_screen::AppState::InGame)),
+            ),
         );
     }
 }
@@ -22,31 +49,74 @@ impl Plugin for ThirdPersonCameraPlugin {
 pub struct ThirdPersonCamera {
     pub focus: Vec3,
     pub mouse_sensitivity: f32,
-    pub mouse_orbit_button: MouseButton,
     pub zoom_enabled: bool,
     pub zoom: Zoom,
     pub zoom_sensitivity: f32,
+    // Share of the orbit and pan speed kept after 1/60th of a second once input stops
     pub inertia: f32,
+    // Rates at which the camera closes the gap to its target rotation and zoom, per second
+    pub orbit_smoothing: f32,
+    pub zoom_smoothing: f32,
+    // Minimum height of the camera above the terrain
+    pub ground_clearance: f32,
+    // Rate at which the camera moves back out after the terrain stops blocking it
+    pub zoom_restore_speed: f32,
+    // Distance from the focus after pulling in for terrain, at most the zoom radius
+    collision_radius: f32,
+    target_rotation: Quat,
+    // Orbit speed left over from the last drag, in pixels per second
+    orbit_velocity: Vec2,
 }
 
 impl Default for ThirdPersonCamera {
     fn default() -> Self {
+        let zoom = Zoom::new(1.5, 9.0);
         ThirdPersonCamera {
             focus: Vec3::ZERO,
             mouse_sensitivity: 2.5,
-            mouse_orbit_button: MouseButton::Right,
             zoom_enabled: true,
-            zoom: Zoom::new(1.5, 9.0),
+            collision_radius: zoom.radius,
+            zoom,
             zoom_sensitivity: 1.0,
             inertia: 0.97,
+            orbit_smoothing: 20.0,
+            zoom_smoothing: 10.0,
+            ground_clearance: 0.3,
+            zoom_restore_speed: 4.0,
+            target_rotation: Quat::IDENTITY,
+            orbit_velocity: Vec2::ZERO,
         }
     }
 }
 
+impl ThirdPersonCamera {
+    // Integrates the inertia decay over `dt` instead of applying it once per frame, so coasting
+    // covers the same distance at any frame rate. Returns how far a unit velocity travels over
+    // `dt` and the share of the velocity left afterwards.
+    pub fn coast(&self, dt: f32) -> (f32, f32) {
+        if self.inertia <= 0.0 {
+            return (0.0, 0.0);
+        }
+        if self.inertia >= 1.0 {
+            return (dt, 1.0);
+        }
+        let decay_rate = -self.inertia.ln() * 60.0;
+        let decay = (-decay_rate * dt).exp();
+        return ((1.0 - decay) / decay_rate, decay);
+    }
+}
+
+// Fraction of the remaining distance to cover this frame when easing towards a target at
+// `rate` per second
+pub fn smoothing_factor(rate: f32, dt: f32) -> f32 {
+    return 1.0 - (-rate * dt).exp();
+}
+
 pub struct Zoom {
     pub min: f32,
     pub max: f32,
     radius: f32,
+    target_radius: f32,
 }
 
 impl Zoom {
@@ -55,35 +125,149 @@ impl Zoom {
             min,
             max,
             radius: (min + max) / 2.0,
+            target_radius: (min + max) / 2.0,
         }
     }
 }
 
+// Entities a third person camera can follow
 #[derive(Component)]
 pub struct ThirdPersonCameraTarget;
 
-fn sync_player_camera(
-    player_q: Query<&Transform, With<ThirdPersonCameraTarget>>,
-    mut cam_q: Query<(&mut ThirdPersonCamera, &mut Transform), Without<ThirdPersonCameraTarget>>,
+// Links a camera to the entity it follows. Every camera has its own, so picture in picture or
+// split screen views can each follow a different target.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CameraTarget(pub Entity);
+
+// Cameras without a target, or whose target is gone, take the first target no other camera is
+// following, or share one when there are more cameras than targets
+fn link_camera_targets(
+    mut commands: Commands,
+    target_q: Query<Entity, With<ThirdPersonCameraTarget>>,
+    cam_q: Query<(Entity, Option<&CameraTarget>), With<ThirdPersonCamera>>,
 ) {
-    let Ok(player) = player_q.get_single() else {
-        return;
-    };
-    let Ok((cam, mut cam_transform)) = cam_q.get_single_mut() else {
+    let mut followed: Vec<Entity> = cam_q
+        .iter()
+        .filter_map(|(_, target)| target.map(|target| target.0))
+        .filter(|target| target_q.contains(*target))
+        .collect();
+    for (entity, target) in cam_q.iter() {
+        if target.map_or(false, |target| target_q.contains(target.0)) {
+            continue;
+        }
+        let next = target_q
+            .iter()
+            .find(|target| !followed.contains(target))
+            .or_else(|| target_q.iter().next());
+        match next {
+            Some(next) => {
+                followed.push(next);
+                commands.entity(entity).insert(CameraTarget(next));
+            }
+            None => {
+                commands.entity(entity).remove::<CameraTarget>();
+            }
+        }
+    }
+}
+
+// Camera that mouse and gamepad input goes to: the frontmost active camera under the cursor,
+// or the frontmost one overall while the cursor is outside the window
+pub fn input_camera<'a>(
+    window: Option<&Window>,
+    cameras: impl Iterator<Item = (Entity, &'a Camera)>,
+) -> Option<Entity> {
+    let cursor = window.and_then(|window| window.cursor_position());
+    return cameras
+        .filter(|(_, camera)| camera.is_active)
+        .filter(|(_, camera)| match cursor {
+            Some(cursor) => camera
+                .logical_viewport_rect()
+                .map_or(false, |rect| rect.contains(cursor)),
+            None => true,
+        })
+        .max_by_key(|(_, camera)| camera.order)
+        .map(|(entity, _)| entity);
+}
+
+// Camera that took input last. Panels like the minimap follow it rather than the camera under
+// the cursor, which while the cursor is over the panel is just whichever view covers it.
+#[derive(Resource, Default, Debug, PartialEq)]
+pub struct LastInputCamera(pub Option<Entity>);
+
+fn track_last_input_camera(
+    actions: Res<ActionState>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    cam_q: Query<(Entity, &Camera), With<ThirdPersonCamera>>,
+    mut last_input_camera: ResMut<LastInputCamera>,
+) {
+    let still_active = last_input_camera
+        .0
+        .and_then(|entity| cam_q.get(entity).ok())
+        .map_or(false, |(_, camera)| camera.is_active);
+    if still_active && !actions.any_pressed() {
         return;
-    };
-    let rotation_matrix = Mat3::from_quat(cam_transform.rotation);
+    }
+    let camera = input_camera(window_q.get_single().ok(), cam_q.iter());
+    if last_input_camera.0 != camera {
+        last_input_camera.0 = camera;
+    }
+}
 
-    let desired_translation =
-        cam.focus + rotation_matrix.mul_vec3(Vec3::new(0.0, 0.0, cam.zoom.radius));
+fn sync_player_camera(
+    target_q: Query<&Transform, With<ThirdPersonCameraTarget>>,
+    mut cam_q: Query<
+        (&ThirdPersonCamera, &CameraTarget, &mut Transform),
+        (
+            Without<ThirdPersonCameraTarget>,
+            Without<StrategyCamera>,
+            Without<CameraPathPlayback>,
+            Without<bookmarks::CameraFlight>,
+        ),
+    >,
+) {
+    for (cam, target, mut cam_transform) in cam_q.iter_mut() {
+        let Ok(target) = target_q.get(target.0) else {
+            continue;
+        };
+        let rotation_matrix = Mat3::from_quat(cam_transform.rotation);
 
-    let delta = player.translation - cam.focus;
-    cam_transform.translation = desired_translation + delta;
+        let desired_translation =
+            cam.focus + rotation_matrix.mul_vec3(Vec3::new(0.0, 0.0, cam.zoom.radius));
+
+        let delta = target.translation - cam.focus;
+        cam_transform.translation = desired_translation + delta;
+    }
 }
 
-pub fn zoom_condition(cam_q: Query<&ThirdPersonCamera, With<ThirdPersonCamera>>) -> bool {
-    let Ok(cam) = cam_q.get_single() else {
-        return false;
-    };
-    return cam.zoom_enabled;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera_system::testing::{self, spawn_cameras, tap_keys};
+
+    #[test]
+    fn last_input_camera_stays_put_until_there_is_input() {
+        let mut app = testing::input_app();
+        app.init_resource::<LastInputCamera>()
+            .add_systems(Update, track_last_input_camera);
+        let [back, front] = spawn_cameras(&mut app);
+        app.update();
+        assert_eq!(app.world().resource::<LastInputCamera>().0, Some(front));
+
+        *app.world_mut().resource_mut::<LastInputCamera>() = LastInputCamera(Some(back));
+        app.update();
+        assert_eq!(app.world().resource::<LastInputCamera>().0, Some(back));
+
+        // Without a window there is no cursor, so input goes to the frontmost camera
+        tap_keys(&mut app, &[KeyCode::KeyW]);
+        assert_eq!(app.world().resource::<LastInputCamera>().0, Some(front));
+
+        app.world_mut()
+            .entity_mut(front)
+            .get_mut::<Camera>()
+            .unwrap()
+            .is_active = false;
+        app.update();
+        assert_eq!(app.world().resource::<LastInputCamera>().0, Some(back));
+    }
 }
@@ -1,112 +1,271 @@
 use std::f32::consts::PI;
 
-use bevy::ecs::query::QuerySingleError;
-use bevy::{
-    input::mouse::{MouseMotion, MouseWheel},
-    prelude::*,
-    window::PrimaryWindow,
-};
-
-use crate::camera_system::ThirdPersonCamera;
+use bevy::{input::mouse::MouseMotion, prelude::*, window::PrimaryWindow};
+
+use crate::camera_system::bookmarks::CameraFlight;
+use crate::camera_system::{CameraPathPlayback, StrategyCamera, ThirdPersonCamera};
+use crate::controls::{ActionState, InputAction, InputBindings};
 use crate::{camera_system, loading_screen::AppState::InGame};
 
-#[derive(Resource)]
-pub struct CamVelocity(Vec2);
+// Orbit speed of a fully tilted right stick, in pixels of mouse drag per second
+const STICK_ORBIT_SPEED: f32 = 600.0;
+
 pub struct MousePlugin;
 
 impl Plugin for MousePlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(CamVelocity(Vec2::ZERO)).add_systems(
+        app.add_systems(
             Update,
-            (
-                orbit_mouse.run_if(in_state(InGame)),
-                zoom_mouse
-                    .run_if(in_state(InGame))
-                    .run_if(camera_system::zoom_condition),
-            )
-                .chain(),
+            (orbit_mouse, zoom_mouse).chain().run_if(in_state(InGame)),
         );
     }
 }
 
-fn orbit_condition(cam: &ThirdPersonCamera, mouse: &Res<ButtonInput<MouseButton>>) -> bool {
-    if mouse.pressed(cam.mouse_orbit_button) {
+fn orbit_condition(actions: &Res<ActionState>) -> bool {
+    if actions.pressed(InputAction::Orbit) {
         return true;
     }
     return false;
 }
 
+// Every third person camera keeps orbiting and coasting on its own, but only the one under the
+// cursor is steered by the mouse and gamepad
 pub fn orbit_mouse(
+    time: Res<Time>,
     window_q: Query<&Window, With<PrimaryWindow>>,
-    mut cam_q: Query<(&ThirdPersonCamera, &mut Transform), With<ThirdPersonCamera>>,
-    mouse: Res<ButtonInput<MouseButton>>,
+    // Every camera counts towards which one takes input, even those the orbit leaves alone
+    all_cam_q: Query<(Entity, &Camera), With<ThirdPersonCamera>>,
+    mut cam_q: Query<
+        (Entity, &Camera, &mut ThirdPersonCamera, &mut Transform),
+        (
+            Without<StrategyCamera>,
+            Without<CameraPathPlayback>,
+            Without<CameraFlight>,
+        ),
+    >,
+    actions: Res<ActionState>,
+    input_bindings: Res<InputBindings>,
     mut mouse_evr: EventReader<MouseMotion>,
-    mut cam_velocity: ResMut<CamVelocity>,
 ) {
-    let rotation: Vec2;
-    let Ok((cam, mut cam_transform)): Result<
-        (&ThirdPersonCamera, Mut<Transform>),
-        QuerySingleError,
-    > = cam_q.get_single_mut() else {
-        return;
-    };
-    let mut position: Vec2 = Vec2::new(0.0, 0.0);
-    for ev in mouse_evr.read() {
-        if orbit_condition(cam, &mouse) {
-            cam_velocity.0 = ev.delta * cam.mouse_sensitivity;
-        }
-        position = ev.delta * cam.mouse_sensitivity;
-    }
+    let dt = time.delta_seconds();
+    let mouse_motion: Vec2 = mouse_evr.read().map(|ev| ev.delta).sum();
+    let stick = Vec2::new(
+        actions.axis(InputAction::LookLeft, InputAction::LookRight),
+        actions.axis(InputAction::LookUp, InputAction::LookDown),
+    ) * STICK_ORBIT_SPEED
+        * input_bindings.gamepad.look_sensitivity;
+    let input_camera = camera_system::input_camera(window_q.get_single().ok(), all_cam_q.iter());
 
-    if !orbit_condition(cam, &mouse) {
-        rotation = cam_velocity.0;
-        cam_velocity.0 *= cam.inertia;
-    } else {
-        rotation = position;
-        cam_velocity.0 = position;
+    for (entity, camera, mut cam, mut cam_transform) in cam_q.iter_mut() {
+        let has_input = input_camera == Some(entity);
+        let orbiting = has_input && orbit_condition(&actions);
+        let steering = orbiting || (has_input && stick != Vec2::ZERO);
+
+        // Mouse motion already adds up to the same distance at any frame rate, while the stick
+        // gives a speed
+        let steer = steering.then(|| {
+            let drag = if orbiting { mouse_motion } else { Vec2::ZERO };
+            (drag + stick * dt) * cam.mouse_sensitivity
+        });
+        orbit_step(
+            &mut cam,
+            &mut cam_transform,
+            steer,
+            camera.logical_viewport_size(),
+            dt,
+        );
     }
+}
 
-    if rotation.length_squared() > 0.0 {
-        let window = window_q.get_single().unwrap();
-        let delta_x = {
-            let delta: f32 = rotation.x / window.width() * PI;
-            delta
-        };
-
-        let delta_y: f32 = rotation.y / window.height() * PI;
-        let yaw: Quat = Quat::from_rotation_y(-delta_x);
-        let pitch: Quat = Quat::from_rotation_x(-delta_y);
-        cam_transform.rotation = yaw * cam_transform.rotation;
-
-        let new_rotation: Quat = cam_transform.rotation * pitch;
-        let up_vector: Vec3 = new_rotation * Vec3::Y;
-        if up_vector.y > 0.0 {
-            cam_transform.rotation = new_rotation;
+// Turns the camera by `steer` pixels of drag, or lets it coast when there is no input. The
+// velocity is tracked per second so the orbit can coast on after the button or stick is
+// released.
+fn orbit_step(
+    cam: &mut ThirdPersonCamera,
+    cam_transform: &mut Transform,
+    steer: Option<Vec2>,
+    viewport: Option<Vec2>,
+    dt: f32,
+) {
+    let rotation: Vec2 = match steer {
+        Some(steer) => {
+            if dt > 0.0 {
+                cam.orbit_velocity = steer / dt;
+            }
+            steer
+        }
+        None => {
+            let (travel, decay) = cam.coast(dt);
+            let rotation = cam.orbit_velocity * travel;
+            cam.orbit_velocity *= decay;
+            rotation
+        }
+    };
+
+    // A drag across the whole view turns the camera half way round
+    if let Some(viewport) = viewport {
+        if rotation.length_squared() > 0.0 && viewport.x > 0.0 && viewport.y > 0.0 {
+            let delta_x: f32 = rotation.x / viewport.x * PI;
+            let delta_y: f32 = rotation.y / viewport.y * PI;
+            let yaw: Quat = Quat::from_rotation_y(-delta_x);
+            let pitch: Quat = Quat::from_rotation_x(-delta_y);
+            cam.target_rotation = yaw * cam.target_rotation;
+
+            let new_rotation: Quat = cam.target_rotation * pitch;
+            let up_vector: Vec3 = new_rotation * Vec3::Y;
+            if up_vector.y > 0.0 {
+                cam.target_rotation = new_rotation;
+            }
         }
     }
 
+    cam_transform.rotation = cam_transform.rotation.slerp(
+        cam.target_rotation,
+        camera_system::smoothing_factor(cam.orbit_smoothing, dt),
+    );
     let rot_matrix: Mat3 = Mat3::from_quat(cam_transform.rotation);
     cam_transform.translation =
         cam.focus + rot_matrix.mul_vec3(Vec3::new(0.0, 0.0, cam.zoom.radius));
 }
 
-fn zoom_mouse(
-    mut scroll_evr: EventReader<MouseWheel>,
-    mut cam_q: Query<&mut ThirdPersonCamera>,
-    cam_transform_q: Query<&Transform, With<ThirdPersonCamera>>,
+// Zooming moves the target radius, and the radius follows it smoothly
+pub fn zoom_mouse(
+    time: Res<Time>,
+    actions: Res<ActionState>,
+    input_bindings: Res<InputBindings>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    mut cam_q: Query<(Entity, &Camera, &mut ThirdPersonCamera)>,
 ) {
-    let mut scroll: f32 = 0.0;
-    for ev in scroll_evr.read() {
-        scroll += ev.y;
+    // Wheel steps zoom by a set amount, held triggers zoom at a steady rate
+    let scroll: f32 = actions.impulse(InputAction::ZoomIn) - actions.impulse(InputAction::ZoomOut)
+        + actions.axis(InputAction::ZoomOut, InputAction::ZoomIn)
+            * input_bindings.gamepad.zoom_speed
+            * time.delta_seconds();
+    let input_camera = camera_system::input_camera(
+        window_q.get_single().ok(),
+        cam_q.iter().map(|(entity, camera, _)| (entity, camera)),
+    );
+
+    for (entity, _, mut cam) in cam_q.iter_mut() {
+        let has_input = cam.zoom_enabled && input_camera == Some(entity);
+        let scroll = if has_input { scroll } else { 0.0 };
+        zoom_step(&mut cam, scroll, time.delta_seconds());
+    }
+}
+
+// Each wheel step scales the target radius by the same factor, so a held trigger zooms at the
+// same rate however its steps are split between frames
+fn zoom_step(cam: &mut ThirdPersonCamera, scroll: f32, dt: f32) {
+    if scroll.abs() > 0.0 {
+        let new_radius: f32 = cam.zoom.target_radius * (-scroll * 0.1 * cam.zoom_sensitivity).exp();
+        cam.zoom.target_radius = new_radius.clamp(cam.zoom.min, cam.zoom.max);
     }
+    let t = camera_system::smoothing_factor(cam.zoom_smoothing, dt);
+    cam.zoom.radius += (cam.zoom.target_radius - cam.zoom.radius) * t;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME_RATES: [f32; 2] = [30.0, 144.0];
+    const VIEWPORT: Vec2 = Vec2::new(1280.0, 720.0);
 
-    if let (Ok(mut cam), Ok(_camera_transform)) =
-        (cam_q.get_single_mut(), cam_transform_q.get_single())
-    {
-        if scroll.abs() > 0.0 {
-            let new_radius: f32 =
-                cam.zoom.radius - scroll * cam.zoom.radius * 0.1 * cam.zoom_sensitivity;
-            cam.zoom.radius = new_radius.clamp(cam.zoom.min, cam.zoom.max);
+    // Drags for half a second at a steady speed, then lets go and coasts until `seconds`
+    fn orbit_for(frame_rate: f32, seconds: f32) -> (ThirdPersonCamera, Transform) {
+        let mut cam = ThirdPersonCamera::default();
+        let mut transform = Transform::default();
+        let dt = 1.0 / frame_rate;
+        let drag_speed = Vec2::new(900.0, 120.0);
+        for frame in 0..(seconds * frame_rate).round() as usize {
+            let time = frame as f32 * dt;
+            let steer = (time < 0.5).then_some(drag_speed * dt);
+            orbit_step(&mut cam, &mut transform, steer, Some(VIEWPORT), dt);
         }
+        return (cam, transform);
+    }
+
+    // Pulls the zoom trigger all the way for half a second, then lets go until `seconds`
+    fn zoom_for(frame_rate: f32, seconds: f32) -> ThirdPersonCamera {
+        let mut cam = ThirdPersonCamera::default();
+        let dt = 1.0 / frame_rate;
+        let zoom_speed = 8.0;
+        for frame in 0..(seconds * frame_rate).round() as usize {
+            let time = frame as f32 * dt;
+            let scroll = if time < 0.5 { zoom_speed * dt } else { 0.0 };
+            zoom_step(&mut cam, scroll, dt);
+        }
+        return cam;
+    }
+
+    #[test]
+    fn orbit_is_frame_rate_independent() {
+        for seconds in [0.5, 1.0, 3.0] {
+            let [(slow, slow_transform), (fast, fast_transform)] =
+                FRAME_RATES.map(|frame_rate| orbit_for(frame_rate, seconds));
+            let target_gap = slow.target_rotation.angle_between(fast.target_rotation);
+            assert!(
+                target_gap < 1e-3,
+                "targets {} rad apart at {}s",
+                target_gap,
+                seconds
+            );
+            let rotation_gap = slow_transform
+                .rotation
+                .angle_between(fast_transform.rotation);
+            // While the target still moves, the eased rotation trails it by up to half a frame
+            // more at low frame rates. Once the orbit has settled they agree.
+            let tolerance = if seconds < 3.0 { 0.05 } else { 1e-3 };
+            assert!(
+                rotation_gap < tolerance,
+                "rotations {} rad apart at {}s",
+                rotation_gap,
+                seconds
+            );
+        }
+        // The drag moved the camera and coasting carried it further
+        let (dragged, _) = orbit_for(60.0, 0.5);
+        let (coasted, _) = orbit_for(60.0, 3.0);
+        assert!(dragged.target_rotation.angle_between(Quat::IDENTITY) > 0.5);
+        assert!(
+            coasted
+                .target_rotation
+                .angle_between(dragged.target_rotation)
+                > 0.1
+        );
+    }
+
+    #[test]
+    fn zoom_is_frame_rate_independent() {
+        for seconds in [0.5, 1.0, 3.0] {
+            let [slow, fast] = FRAME_RATES.map(|frame_rate| zoom_for(frame_rate, seconds));
+            assert!(
+                (slow.zoom.target_radius - fast.zoom.target_radius).abs() < 1e-3,
+                "target radii {} and {} at {}s",
+                slow.zoom.target_radius,
+                fast.zoom.target_radius,
+                seconds
+            );
+            assert!(
+                (slow.zoom.radius - fast.zoom.radius).abs() < 0.05,
+                "radii {} and {} at {}s",
+                slow.zoom.radius,
+                fast.zoom.radius,
+                seconds
+            );
+        }
+        let zoomed = zoom_for(60.0, 3.0);
+        assert!(zoomed.zoom.target_radius < ThirdPersonCamera::default().zoom.target_radius);
+        assert!((zoomed.zoom.radius - zoomed.zoom.target_radius).abs() < 1e-3);
+    }
+
+    #[test]
+    fn wheel_steps_zoom_in_and_back_out_to_the_same_radius() {
+        let mut cam = ThirdPersonCamera::default();
+        let start = cam.zoom.target_radius;
+        zoom_step(&mut cam, 2.0, 1.0 / 60.0);
+        assert!(cam.zoom.target_radius < start);
+        zoom_step(&mut cam, -2.0, 1.0 / 60.0);
+        assert!((cam.zoom.target_radius - start).abs() < 1e-5);
     }
 }
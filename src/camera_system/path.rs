@@ -0,0 +1,427 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use serde::{Deserialize, Serialize};
+
+use crate::camera_system::bookmarks::current_view;
+use crate::camera_system::{self, StrategyCamera, ThirdPersonCamera};
+use crate::config_parser::{load_ron, save_ron, user_save_path};
+use crate::controls::{ActionState, InputAction};
+use crate::loading_screen::AppState::InGame;
+
+const PATH_FILE: &str = "camera_path.ron";
+// Time between a new keyframe and the one before it. Edit the file to change the timing.
+const KEYFRAME_SPACING: f32 = 3.0;
+
+pub struct CameraPathPlugin;
+
+impl Plugin for CameraPathPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(CameraPath::load()).add_systems(
+            Update,
+            (
+                edit_camera_path,
+                toggle_path_playback,
+                play_camera_path.before(super::collision::keep_camera_above_terrain),
+                save_camera_path,
+            )
+                .chain()
+                .run_if(in_state(InGame)),
+        );
+    }
+}
+
+// The focus the camera looks at, its rotation and its distance from the focus, `time` seconds
+// into the path
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CameraKeyframe {
+    pub time: f32,
+    pub position: Vec3,
+    pub rotation: Quat,
+    pub zoom: f32,
+}
+
+// Flyover made of keyframes, kept in camera_path.ron in the saves folder. The add keyframe
+// action, K by default, adds the view of the camera taking input as a keyframe, Shift+K removes
+// the last one and P plays the path on that camera or stops it.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, Resource)]
+pub struct CameraPath {
+    pub keyframes: Vec<CameraKeyframe>,
+}
+
+// While on a camera the path drives it, and the orbit, follow and strategy systems leave it be
+#[derive(Component, Default)]
+pub struct CameraPathPlayback {
+    elapsed: f32,
+}
+
+impl CameraPath {
+    pub fn load() -> CameraPath {
+        let mut camera_path: CameraPath =
+            load_ron(user_save_path(PATH_FILE), "camera path").unwrap_or_default();
+        camera_path
+            .keyframes
+            .sort_by(|a, b| a.time.total_cmp(&b.time));
+        return camera_path;
+    }
+
+    pub fn save(&self) {
+        save_ron(user_save_path(PATH_FILE), "camera path", self);
+    }
+
+    pub fn push(&mut self, position: Vec3, rotation: Quat, zoom: f32) {
+        let time = self
+            .keyframes
+            .last()
+            .map_or(0.0, |last| last.time + KEYFRAME_SPACING);
+        self.keyframes.push(CameraKeyframe {
+            time,
+            position,
+            rotation,
+            zoom,
+        });
+    }
+
+    // Catmull-Rom spline through the keyframes. Returns None before the first keyframe and
+    // after the last, or when there are fewer than two.
+    pub fn sample(&self, time: f32) -> Option<CameraKeyframe> {
+        let keyframes = &self.keyframes;
+        if keyframes.len() < 2 {
+            return None;
+        }
+        let segment = keyframes
+            .windows(2)
+            .position(|pair| pair[0].time <= time && time <= pair[1].time)?;
+        let (start, end) = (keyframes[segment], keyframes[segment + 1]);
+        let before = keyframes[segment.saturating_sub(1)];
+        let after = keyframes[(segment + 2).min(keyframes.len() - 1)];
+        let span = end.time - start.time;
+        let t = if span > 0.0 {
+            (time - start.time) / span
+        } else {
+            1.0
+        };
+
+        // The zoom rides along as a fourth coordinate of the position
+        let placement = |keyframe: &CameraKeyframe| keyframe.position.extend(keyframe.zoom);
+        let placement = catmull_rom(
+            [
+                placement(&before),
+                placement(&start),
+                placement(&end),
+                placement(&after),
+            ],
+            t,
+        );
+
+        // Quaternions q and -q are the same rotation, so keep every control point on the
+        // same side as the start before blending
+        let reference = Vec4::from(start.rotation);
+        let aligned = |rotation: Quat| {
+            let rotation = Vec4::from(rotation);
+            if rotation.dot(reference) < 0.0 {
+                -rotation
+            } else {
+                rotation
+            }
+        };
+        let rotation = catmull_rom(
+            [
+                aligned(before.rotation),
+                reference,
+                aligned(end.rotation),
+                aligned(after.rotation),
+            ],
+            t,
+        );
+
+        return Some(CameraKeyframe {
+            time,
+            position: placement.truncate(),
+            rotation: Quat::from_vec4(rotation).normalize(),
+            zoom: placement.w.max(0.0),
+        });
+    }
+}
+
+fn catmull_rom(points: [Vec4; 4], t: f32) -> Vec4 {
+    let [p0, p1, p2, p3] = points;
+    let t2 = t * t;
+    let t3 = t2 * t;
+    return 0.5
+        * (2.0 * p1
+            + (p2 - p0) * t
+            + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+            + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3);
+}
+
+fn edit_camera_path(
+    actions: Res<ActionState>,
+    mut camera_path: ResMut<CameraPath>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    cam_q: Query<(
+        Entity,
+        &Camera,
+        &ThirdPersonCamera,
+        &Transform,
+        Has<CameraPathPlayback>,
+    )>,
+) {
+    let adding = actions.just_pressed(InputAction::AddPathKeyframe);
+    let removing = actions.just_pressed(InputAction::RemovePathKeyframe);
+    if !adding && !removing {
+        return;
+    }
+    if removing {
+        camera_path.keyframes.pop();
+        return;
+    }
+    let input_camera = camera_system::input_camera(
+        window_q.get_single().ok(),
+        cam_q
+            .iter()
+            .map(|(entity, camera, _, _, _)| (entity, camera)),
+    );
+    let Some((_, _, cam, transform, is_playing)) =
+        input_camera.and_then(|entity| cam_q.get(entity).ok())
+    else {
+        return;
+    };
+    if is_playing {
+        return;
+    }
+    let view = current_view(cam, transform);
+    camera_path.push(view.focus, view.rotation, view.zoom);
+}
+
+fn toggle_path_playback(
+    mut commands: Commands,
+    actions: Res<ActionState>,
+    camera_path: Res<CameraPath>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    mut cam_q: Query<(
+        Entity,
+        &Camera,
+        &mut ThirdPersonCamera,
+        &Transform,
+        Has<CameraPathPlayback>,
+    )>,
+) {
+    if !actions.just_pressed(InputAction::TogglePathPlayback) {
+        return;
+    }
+    let input_camera = camera_system::input_camera(
+        window_q.get_single().ok(),
+        cam_q
+            .iter()
+            .map(|(entity, camera, _, _, _)| (entity, camera)),
+    );
+    let Some((entity, _, mut cam, transform, is_playing)) =
+        input_camera.and_then(|entity| cam_q.get_mut(entity).ok())
+    else {
+        return;
+    };
+    if is_playing {
+        cam.target_rotation = transform.rotation;
+        commands.entity(entity).remove::<CameraPathPlayback>();
+    } else if camera_path.keyframes.len() >= 2 {
+        commands.entity(entity).insert(CameraPathPlayback {
+            elapsed: camera_path.keyframes[0].time,
+        });
+    }
+}
+
+fn play_camera_path(
+    mut commands: Commands,
+    time: Res<Time>,
+    camera_path: Res<CameraPath>,
+    mut cam_q: Query<(
+        Entity,
+        &mut ThirdPersonCamera,
+        Option<&mut StrategyCamera>,
+        &mut CameraPathPlayback,
+        &mut Transform,
+    )>,
+) {
+    for (entity, mut cam, strategy, mut playback, mut transform) in cam_q.iter_mut() {
+        playback.elapsed += time.delta_seconds();
+        let Some(frame) = camera_path.sample(playback.elapsed) else {
+            // Hand the camera back facing where the path left it
+            cam.target_rotation = transform.rotation;
+            if let Some(mut strategy) = strategy {
+                strategy.yaw = StrategyCamera::facing(transform.rotation).yaw;
+            }
+            commands.entity(entity).remove::<CameraPathPlayback>();
+            continue;
+        };
+        cam.focus = frame.position;
+        cam.zoom.radius = frame.zoom;
+        cam.zoom.target_radius = frame.zoom;
+        transform.rotation = frame.rotation;
+        transform.translation = frame.position + frame.rotation * Vec3::new(0.0, 0.0, frame.zoom);
+    }
+}
+
+fn save_camera_path(camera_path: Res<CameraPath>) {
+    if camera_path.is_changed() && !camera_path.is_added() {
+        camera_path.save();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera_system::testing::{self, spawn_cameras, tap_keys};
+
+    // Editing and playback without saving the path to the user's files
+    fn input_app() -> App {
+        let mut app = testing::input_app();
+        app.init_resource::<CameraPath>()
+            .add_systems(Update, (edit_camera_path, toggle_path_playback).chain());
+        return app;
+    }
+
+    fn keyframe(time: f32, position: Vec3, rotation: Quat, zoom: f32) -> CameraKeyframe {
+        return CameraKeyframe {
+            time,
+            position,
+            rotation,
+            zoom,
+        };
+    }
+
+    fn test_path() -> CameraPath {
+        return CameraPath {
+            keyframes: vec![
+                keyframe(0.0, Vec3::ZERO, Quat::IDENTITY, 5.0),
+                keyframe(
+                    2.0,
+                    Vec3::new(10.0, 2.0, 0.0),
+                    Quat::from_rotation_y(1.0),
+                    8.0,
+                ),
+                keyframe(
+                    3.0,
+                    Vec3::new(12.0, 2.0, 9.0),
+                    Quat::from_rotation_x(-0.6),
+                    3.0,
+                ),
+                keyframe(
+                    6.0,
+                    Vec3::new(0.0, 4.0, 20.0),
+                    Quat::from_rotation_y(-2.5),
+                    7.0,
+                ),
+            ],
+        };
+    }
+
+    #[test]
+    fn sample_passes_through_every_keyframe() {
+        let camera_path = test_path();
+        for expected in &camera_path.keyframes {
+            let frame = camera_path.sample(expected.time).unwrap();
+            assert!(frame.position.distance(expected.position) < 1e-4);
+            assert!(frame.rotation.angle_between(expected.rotation) < 1e-3);
+            assert!((frame.zoom - expected.zoom).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn sample_is_none_outside_the_keyframes() {
+        let camera_path = test_path();
+        assert!(camera_path.sample(-0.1).is_none());
+        assert!(camera_path.sample(6.1).is_none());
+        assert!(camera_path.sample(4.5).is_some());
+
+        let single = CameraPath {
+            keyframes: vec![keyframe(0.0, Vec3::ZERO, Quat::IDENTITY, 5.0)],
+        };
+        assert!(single.sample(0.0).is_none());
+        assert!(CameraPath::default().sample(0.0).is_none());
+    }
+
+    #[test]
+    fn sample_ignores_quaternion_sign_flips() {
+        let camera_path = test_path();
+        let mut flipped = test_path();
+        for keyframe in flipped.keyframes.iter_mut().skip(1).step_by(2) {
+            keyframe.rotation = Quat::from_vec4(-Vec4::from(keyframe.rotation));
+        }
+        for step in 0..=60 {
+            let time = step as f32 * 0.1;
+            let expected = camera_path.sample(time).unwrap();
+            let frame = flipped.sample(time).unwrap();
+            // Comparing dot products as acos loses too much precision next to 1
+            assert!(
+                frame.rotation.dot(expected.rotation).abs() > 1.0 - 1e-5,
+                "{}s",
+                time
+            );
+            // Without the alignment the blend would swing the long way round between keyframes
+            let start = camera_path
+                .keyframes
+                .iter()
+                .rev()
+                .find(|keyframe| keyframe.time <= time)
+                .unwrap();
+            assert!(
+                frame.rotation.angle_between(start.rotation) < 2.6,
+                "{}s",
+                time
+            );
+        }
+    }
+
+    #[test]
+    fn catmull_rom_hits_the_inner_points_and_follows_lines() {
+        let points = [
+            Vec4::new(0.0, 0.0, 0.0, 1.0),
+            Vec4::new(1.0, 2.0, 0.0, 2.0),
+            Vec4::new(2.0, 4.0, 0.0, 3.0),
+            Vec4::new(3.0, 6.0, 0.0, 4.0),
+        ];
+        assert!(catmull_rom(points, 0.0).distance(points[1]) < 1e-6);
+        assert!(catmull_rom(points, 1.0).distance(points[2]) < 1e-6);
+        // Evenly spaced points on a line give a straight, evenly paced segment
+        for t in [0.25, 0.5, 0.75] {
+            let expected = points[1].lerp(points[2], t);
+            assert!(catmull_rom(points, t).distance(expected) < 1e-5);
+        }
+    }
+
+    #[test]
+    fn keyframes_come_from_the_input_camera() {
+        let mut app = input_app();
+        let [_, front] = spawn_cameras(&mut app);
+        app.world_mut()
+            .entity_mut(front)
+            .insert(Transform::from_xyz(4.0, 9.0, 2.0));
+        tap_keys(&mut app, &[KeyCode::KeyK]);
+        tap_keys(&mut app, &[KeyCode::KeyK]);
+        tap_keys(&mut app, &[KeyCode::KeyK]);
+        tap_keys(&mut app, &[KeyCode::ShiftLeft, KeyCode::KeyK]);
+
+        let entity = app.world().entity(front);
+        let expected = current_view(
+            entity.get::<ThirdPersonCamera>().unwrap(),
+            entity.get::<Transform>().unwrap(),
+        );
+        let camera_path = app.world().resource::<CameraPath>();
+        assert_eq!(camera_path.keyframes.len(), 2);
+        for keyframe in &camera_path.keyframes {
+            assert_eq!(keyframe.position, expected.focus);
+        }
+    }
+
+    #[test]
+    fn playback_starts_on_the_input_camera_only() {
+        let mut app = input_app();
+        *app.world_mut().resource_mut::<CameraPath>() = test_path();
+        let [back, front] = spawn_cameras(&mut app);
+        tap_keys(&mut app, &[KeyCode::KeyP]);
+        assert!(app.world().entity(front).contains::<CameraPathPlayback>());
+        assert!(!app.world().entity(back).contains::<CameraPathPlayback>());
+
+        tap_keys(&mut app, &[KeyCode::KeyP]);
+        assert!(!app.world().entity(front).contains::<CameraPathPlayback>());
+    }
+}
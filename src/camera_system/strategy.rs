@@ -0,0 +1,397 @@
+use std::f32::consts::{FRAC_PI_2, PI};
+
+use bevy::{
+    input::mouse::MouseMotion,
+    prelude::*,
+    window::{CursorGrabMode, PrimaryWindow},
+};
+
+use crate::camera_system::bookmarks::CameraFlight;
+use crate::camera_system::{
+    self, zoom_mouse, CameraPathPlayback, CameraTarget, ThirdPersonCamera, ThirdPersonCameraTarget,
+};
+use crate::config_parser::EngineConfig;
+use crate::controls::{ActionState, InputAction};
+use crate::loading_screen::AppState::InGame;
+use crate::terrain_generator::TerrainQuery;
+
+pub struct StrategyCameraPlugin;
+
+impl Plugin for StrategyCameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                toggle_camera_mode,
+                (
+                    pan_strategy_camera,
+                    rotate_strategy_camera,
+                    place_strategy_camera,
+                )
+                    .chain()
+                    .after(zoom_mouse),
+            )
+                .chain()
+                .run_if(in_state(InGame)),
+        );
+    }
+}
+
+// Top down camera that moves freely over the map instead of following the player. While it is
+// on the camera entity the third person systems leave that camera alone. The focus, zoom and
+// inertia are shared with the entity's `ThirdPersonCamera`.
+#[derive(Component)]
+pub struct StrategyCamera {
+    // Pan speed in focus distances per second, so panning feels the same at every zoom
+    pub pan_speed: f32,
+    // Width in pixels of the band along the window edges that scrolls the map
+    pub edge_scroll_margin: f32,
+    pub rotate_speed: f32,
+    // Pitch below the horizon at the closest and furthest zoom, in radians
+    pub pitch_near: f32,
+    pub pitch_far: f32,
+    pub(super) yaw: f32,
+    // Pitch on top of the zoom's, so a recalled bookmark looks exactly the stored way
+    pub(super) tilt: f32,
+    pub(super) pan_velocity: Vec2,
+}
+
+impl Default for StrategyCamera {
+    fn default() -> Self {
+        StrategyCamera {
+            pan_speed: 1.5,
+            edge_scroll_margin: 8.0,
+            rotate_speed: 1.5,
+            pitch_near: 35.0_f32.to_radians(),
+            pitch_far: 65.0_f32.to_radians(),
+            yaw: 0.0,
+            tilt: 0.0,
+            pan_velocity: Vec2::ZERO,
+        }
+    }
+}
+
+impl StrategyCamera {
+    // Strategy view looking the same way along the ground as `rotation`
+    pub(super) fn facing(rotation: Quat) -> StrategyCamera {
+        let forward = rotation * Vec3::NEG_Z;
+        return StrategyCamera {
+            yaw: (-forward.x).atan2(-forward.z),
+            ..default()
+        };
+    }
+
+    // Turns the view to look along `rotation`, keeping the tilt for as long as the camera
+    // stays in the strategy view
+    pub(super) fn look_along(&mut self, rotation: Quat, cam: &ThirdPersonCamera) {
+        let forward = rotation * Vec3::NEG_Z;
+        self.yaw = (-forward.x).atan2(-forward.z);
+        self.tilt = (-forward.y).clamp(-1.0, 1.0).asin() - self.zoom_pitch(cam);
+    }
+
+    fn zoom_pitch(&self, cam: &ThirdPersonCamera) -> f32 {
+        let range = cam.zoom.max - cam.zoom.min;
+        let t = if range > 0.0 {
+            ((cam.zoom.radius - cam.zoom.min) / range).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        return self.pitch_near + (self.pitch_far - self.pitch_near) * t;
+    }
+
+    fn pitch(&self, cam: &ThirdPersonCamera) -> f32 {
+        let limit = FRAC_PI_2 - 0.01;
+        return (self.zoom_pitch(cam) + self.tilt).clamp(-limit, limit);
+    }
+
+    fn rotation(&self, cam: &ThirdPersonCamera) -> Quat {
+        return Quat::from_rotation_y(self.yaw) * Quat::from_rotation_x(-self.pitch(cam));
+    }
+}
+
+// Toggling the camera mode switches the camera taking input between following the player and
+// the free strategy view. The strategy view starts over the player, facing the same way as the
+// third person camera.
+fn toggle_camera_mode(
+    mut commands: Commands,
+    actions: Res<ActionState>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    target_q: Query<&Transform, With<ThirdPersonCameraTarget>>,
+    mut cam_q: Query<
+        (
+            Entity,
+            &Camera,
+            &mut ThirdPersonCamera,
+            &Transform,
+            Option<&CameraTarget>,
+            Has<StrategyCamera>,
+        ),
+        Without<ThirdPersonCameraTarget>,
+    >,
+) {
+    if !actions.just_pressed(InputAction::ToggleCameraMode) {
+        return;
+    }
+    let input_camera = camera_system::input_camera(
+        window_q.get_single().ok(),
+        cam_q
+            .iter()
+            .map(|(entity, camera, _, _, _, _)| (entity, camera)),
+    );
+    let Some((entity, _, mut cam, transform, target, is_strategy)) =
+        input_camera.and_then(|entity| cam_q.get_mut(entity).ok())
+    else {
+        return;
+    };
+    if is_strategy {
+        // Orbit on from the current view rather than swinging back to the old one
+        cam.target_rotation = transform.rotation;
+        commands.entity(entity).remove::<StrategyCamera>();
+        return;
+    }
+    if let Some(target) = target.and_then(|target| target_q.get(target.0).ok()) {
+        cam.focus = target.translation;
+    }
+    commands
+        .entity(entity)
+        .insert(StrategyCamera::facing(transform.rotation));
+}
+
+fn pan_strategy_camera(
+    time: Res<Time>,
+    actions: Res<ActionState>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    engine_config: Res<EngineConfig>,
+    mut cam_q: Query<(
+        Entity,
+        &Camera,
+        &mut ThirdPersonCamera,
+        Option<&mut StrategyCamera>,
+    )>,
+) {
+    let window = window_q.get_single().ok();
+    let input_camera = camera_system::input_camera(
+        window,
+        cam_q.iter().map(|(entity, camera, _, _)| (entity, camera)),
+    );
+    for (entity, camera, mut cam, strategy) in cam_q.iter_mut() {
+        let Some(mut strategy) = strategy else {
+            continue;
+        };
+        // Other cameras only coast
+        let mut input = Vec2::ZERO;
+        if input_camera == Some(entity) {
+            input = Vec2::new(
+                actions.axis(InputAction::MoveLeft, InputAction::MoveRight),
+                actions.axis(InputAction::MoveBack, InputAction::MoveForward),
+            );
+            if let (Some(window), Some(viewport)) = (window, camera.logical_viewport_rect()) {
+                input += edge_scroll(window, viewport, strategy.edge_scroll_margin);
+            }
+        }
+
+        let dt = time.delta_seconds();
+        let (travel, decay) = if input.length_squared() > 0.0 {
+            strategy.pan_velocity =
+                input.clamp_length_max(1.0) * strategy.pan_speed * cam.zoom.radius;
+            (dt, 1.0)
+        } else {
+            cam.coast(dt)
+        };
+
+        // Pan along the ground relative to where the camera is facing
+        let yaw = Quat::from_rotation_y(strategy.yaw);
+        let movement = yaw * Vec3::new(strategy.pan_velocity.x, 0.0, -strategy.pan_velocity.y);
+        strategy.pan_velocity *= decay;
+        let world_max = (engine_config.world_size - 1) as f32;
+        cam.focus += movement * travel;
+        cam.focus.x = cam.focus.x.clamp(0.0, world_max);
+        cam.focus.z = cam.focus.z.clamp(0.0, world_max);
+    }
+}
+
+// Scrolls while the cursor is near the edges of the camera's viewport, so split screen views
+// scroll at their own edges rather than only at the window's
+fn edge_scroll(window: &Window, viewport: Rect, margin: f32) -> Vec2 {
+    if !window.focused || window.cursor.grab_mode == CursorGrabMode::Locked {
+        return Vec2::ZERO;
+    }
+    let Some(cursor) = window.cursor_position() else {
+        return Vec2::ZERO;
+    };
+    if !viewport.contains(cursor) {
+        return Vec2::ZERO;
+    }
+    let mut scroll = Vec2::ZERO;
+    if cursor.x < viewport.min.x + margin {
+        scroll.x -= 1.0;
+    } else if cursor.x > viewport.max.x - margin {
+        scroll.x += 1.0;
+    }
+    // Window coordinates grow downwards
+    if cursor.y < viewport.min.y + margin {
+        scroll.y += 1.0;
+    } else if cursor.y > viewport.max.y - margin {
+        scroll.y -= 1.0;
+    }
+    return scroll;
+}
+
+pub(super) fn rotate_strategy_camera(
+    time: Res<Time>,
+    actions: Res<ActionState>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    mut mouse_evr: EventReader<MouseMotion>,
+    mut cam_q: Query<(
+        Entity,
+        &Camera,
+        &ThirdPersonCamera,
+        Option<&mut StrategyCamera>,
+    )>,
+) {
+    let mouse_delta: f32 = mouse_evr.read().map(|ev| ev.delta.x).sum();
+    let input_camera = camera_system::input_camera(
+        window_q.get_single().ok(),
+        cam_q.iter().map(|(entity, camera, _, _)| (entity, camera)),
+    );
+    let Some((_, camera, cam, Some(mut strategy))) =
+        input_camera.and_then(|entity| cam_q.get_mut(entity).ok())
+    else {
+        return;
+    };
+    let view_width = camera
+        .logical_viewport_size()
+        .map_or(1.0, |viewport| viewport.x.max(1.0));
+    strategy.yaw -= actions.axis(InputAction::RotateLeft, InputAction::RotateRight)
+        * strategy.rotate_speed
+        * time.delta_seconds();
+    if actions.pressed(InputAction::RotateDrag) {
+        strategy.yaw -= mouse_delta * cam.mouse_sensitivity / view_width * PI;
+    }
+}
+
+// Keeps the focus on the ground and looks at it from the zoom distance, pitching down further
+// the further out the camera is zoomed.
+pub fn place_strategy_camera(
+    terrain: TerrainQuery,
+    mut cam_q: Query<
+        (&mut ThirdPersonCamera, &StrategyCamera, &mut Transform),
+        (Without<CameraPathPlayback>, Without<CameraFlight>),
+    >,
+) {
+    for (mut cam, strategy, mut transform) in cam_q.iter_mut() {
+        if let Some(height) = terrain.height_at(cam.focus.x, cam.focus.z) {
+            cam.focus.y = height.max(terrain.sea_level());
+        }
+        transform.rotation = strategy.rotation(&cam);
+        transform.translation =
+            cam.focus + transform.rotation * Vec3::new(0.0, 0.0, cam.zoom.radius);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::time::{TimePlugin, TimeUpdateStrategy};
+
+    use super::*;
+    use crate::camera_system::testing::{self, spawn_cameras, tap_keys};
+    use crate::config_parser::load_engine_config;
+
+    fn strategy_app() -> App {
+        let mut app = testing::input_app();
+        app.add_plugins(TimePlugin)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+                1.0 / 60.0,
+            )))
+            .insert_resource(load_engine_config())
+            .add_systems(
+                Update,
+                (
+                    toggle_camera_mode,
+                    pan_strategy_camera,
+                    rotate_strategy_camera,
+                )
+                    .chain(),
+            );
+        return app;
+    }
+
+    #[test]
+    fn look_along_restores_the_full_rotation() {
+        let cam = ThirdPersonCamera::default();
+        for (yaw, pitch) in [(0.3, 0.2), (-2.5, 1.1), (3.0, -0.4), (1.0, 0.0)] {
+            let rotation = Quat::from_rotation_y(yaw) * Quat::from_rotation_x(-pitch);
+            let mut strategy = StrategyCamera::default();
+            strategy.look_along(rotation, &cam);
+            let gap = strategy.rotation(&cam).angle_between(rotation);
+            assert!(
+                gap < 1e-3,
+                "{} rad off for yaw {} pitch {}",
+                gap,
+                yaw,
+                pitch
+            );
+        }
+    }
+
+    #[test]
+    fn camera_mode_toggles_on_the_input_camera() {
+        let mut app = strategy_app();
+        let [back, front] = spawn_cameras(&mut app);
+        tap_keys(&mut app, &[KeyCode::Tab]);
+        assert!(app.world().entity(front).contains::<StrategyCamera>());
+        assert!(!app.world().entity(back).contains::<StrategyCamera>());
+
+        tap_keys(&mut app, &[KeyCode::Tab]);
+        assert!(!app.world().entity(front).contains::<StrategyCamera>());
+    }
+
+    #[test]
+    fn only_the_input_camera_pans_and_rotates() {
+        let mut app = strategy_app();
+        let cameras = spawn_cameras(&mut app);
+        for camera in cameras {
+            let mut cam = ThirdPersonCamera::default();
+            cam.focus = Vec3::new(20.0, 0.0, 20.0);
+            app.world_mut()
+                .entity_mut(camera)
+                .insert((cam, StrategyCamera::default()));
+        }
+        let mut keys = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+        keys.press(KeyCode::KeyW);
+        keys.press(KeyCode::KeyQ);
+        for _ in 0..10 {
+            app.update();
+        }
+
+        let [back, front] = cameras.map(|camera| {
+            let entity = app.world().entity(camera);
+            let cam = entity.get::<ThirdPersonCamera>().unwrap();
+            let strategy = entity.get::<StrategyCamera>().unwrap();
+            return (cam.focus, strategy.yaw);
+        });
+        assert_eq!(back, (Vec3::new(20.0, 0.0, 20.0), 0.0));
+        assert!(front.0.distance(back.0) > 0.1);
+        assert!(front.1 > 0.0);
+    }
+
+    #[test]
+    fn edge_scrolling_follows_the_viewport() {
+        let mut window = Window::default();
+        // The right half of the window
+        let viewport = Rect::new(640.0, 0.0, 1280.0, 720.0);
+        for (cursor, expected) in [
+            (Vec2::new(645.0, 360.0), Vec2::new(-1.0, 0.0)),
+            (Vec2::new(1000.0, 715.0), Vec2::new(0.0, -1.0)),
+            (Vec2::new(1278.0, 2.0), Vec2::new(1.0, 1.0)),
+            (Vec2::new(960.0, 360.0), Vec2::ZERO),
+            // Over the left half, which is another camera's view
+            (Vec2::new(300.0, 360.0), Vec2::ZERO),
+        ] {
+            window.set_cursor_position(Some(cursor));
+            assert_eq!(edge_scroll(&window, viewport, 8.0), expected, "{}", cursor);
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use bevy::input::{InputPlugin, InputSystem};
+use bevy::prelude::*;
+
+use crate::camera_system::ThirdPersonCamera;
+use crate::controls::{update_action_state, ActionState, InputBindings};
+
+// App that turns key presses into actions with the default bindings, without touching the
+// user's files
+pub fn input_app() -> App {
+    let mut app = App::new();
+    app.add_plugins(InputPlugin)
+        .insert_resource(InputBindings::default())
+        .init_resource::<ActionState>()
+        .add_systems(PreUpdate, update_action_state.after(InputSystem));
+    return app;
+}
+
+// Two third person cameras drawn over each other, the second on top, like a picture in
+// picture view
+pub fn spawn_cameras(app: &mut App) -> [Entity; 2] {
+    return [0, 1].map(|order| {
+        return app
+            .world_mut()
+            .spawn((
+                Camera { order, ..default() },
+                ThirdPersonCamera::default(),
+                Transform::from_xyz(10.0 * order as f32, 8.0, 20.0),
+            ))
+            .id();
+    });
+}
+
+// Presses the keys for one frame and lets go of them the next
+pub fn tap_keys(app: &mut App, keys: &[KeyCode]) {
+    let mut input = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+    for key in keys {
+        input.press(*key);
+    }
+    app.update();
+    let mut input = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+    for key in keys {
+        input.release(*key);
+    }
+    app.update();
+}
@@ -1,6 +1,10 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
 use bevy::prelude::*;
+use directories::{ProjectDirs, UserDirs};
 
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_yaml::{self};
 
 #[derive(Clone, Debug, Serialize, Deserialize, Resource)]
@@ -24,6 +28,45 @@ pub struct MapConfig {
     pub mountain_glaciation: f64,
     pub continent_height_scale: f64,
     pub river_depth: f64,
+    pub beach_height: f64,
+    pub snow_line: f64,
+    pub rock_slope_start: f64,
+    pub rock_slope_end: f64,
+    pub heightmap_path: Option<String>,
+    // Elevations the lowest and highest heightmap samples are stretched to, -1.0 and +1.0 when
+    // left out
+    pub heightmap_range: Option<[f64; 2]>,
+}
+
+impl MapConfig {
+    // Tells worlds apart in the names of the files kept for them: the heightmap's name for
+    // imported worlds, and the seed for generated ones
+    pub fn world_name(&self) -> String {
+        let heightmap_name = self
+            .heightmap_path
+            .as_deref()
+            .and_then(|path| Path::new(path).file_stem())
+            .and_then(|name| name.to_str());
+        if let Some(name) = heightmap_name {
+            return format!("heightmap_{}", name);
+        }
+        return format!("seed_{}", self.seed);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TerrainColliderType {
+    Heightfield,
+    Trimesh,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShadowQuality {
+    Low,
+    Medium,
+    High,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Resource)]
@@ -31,20 +74,216 @@ pub struct EngineConfig {
     pub world_size: usize,
     pub chunk_size: usize,
     pub world_height: f32,
+    pub terrain_collider: TerrainColliderType,
+    pub contour_interval: f32,
+    pub day_length: f32,
+    pub start_hour: f32,
+    pub shadow_quality: ShadowQuality,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Resource)]
+pub struct PlayerConfig {
+    pub move_speed: f32,
+    pub max_slope_angle: f32,
+    pub slope_slowdown: f32,
+    pub max_water_depth: f32,
+    pub gravity: f32,
+    pub step_height: f32,
+    pub snap_distance: f32,
 }
 
 pub fn read_configs(mut commands: Commands) {
-    // Map config
+    commands.insert_resource(load_map_config());
+    commands.insert_resource(load_engine_config());
+    commands.insert_resource(load_player_config());
+}
+
+pub fn load_map_config() -> MapConfig {
     let map_file = std::fs::File::open("assets/configs/map_generation.yml")
         .expect("Could not open map config file.");
-    let map_config: MapConfig =
-        serde_yaml::from_reader(map_file).expect("Could not read map settings.");
-    commands.insert_resource(map_config);
+    return serde_yaml::from_reader(map_file).expect("Could not read map settings.");
+}
 
-    // Engine config
+pub fn load_engine_config() -> EngineConfig {
     let engine_file = std::fs::File::open("assets/configs/engine_config.yml")
         .expect("Could not open engine config file.");
-    let engine_config: EngineConfig =
-        serde_yaml::from_reader(engine_file).expect("Could not read engine settings.");
-    commands.insert_resource(engine_config);
+    return serde_yaml::from_reader(engine_file).expect("Could not read engine settings.");
+}
+
+pub fn load_player_config() -> PlayerConfig {
+    let player_file = std::fs::File::open("assets/configs/player_config.yml")
+        .expect("Could not open player config file.");
+    return serde_yaml::from_reader(player_file).expect("Could not read player settings.");
+}
+
+// Per user files such as graphics settings and key bindings live in the platform config
+// directory rather than in assets, so they survive updates.
+pub fn user_config_path(file_name: &str) -> Option<PathBuf> {
+    let project_dirs = ProjectDirs::from("", "", "Foundations of a Kingdom")?;
+    return Some(project_dirs.config_dir().join(file_name));
+}
+
+// Files that belong to the player's game, kept next to the world previews in the documents
+// directory
+pub fn user_save_path(file_name: &str) -> Option<PathBuf> {
+    return Some(user_documents_dir()?.join("Saves").join(file_name));
+}
+
+// Terrain exports go next to the saves rather than in them
+pub fn user_export_dir() -> Option<PathBuf> {
+    return Some(user_documents_dir()?.join("Exports"));
+}
+
+fn user_documents_dir() -> Option<PathBuf> {
+    let user_dirs = UserDirs::new()?;
+    let documents_dir = user_dirs.document_dir()?;
+    return Some(
+        documents_dir
+            .join("My Games")
+            .join("Foundations of a Kingdom"),
+    );
+}
+
+// Reads a per user file such as the graphics settings. Returns None when there is no file yet,
+// and logs files that cannot be read instead of stopping the game, so the caller falls back to
+// its defaults.
+pub fn load_yaml<T: DeserializeOwned>(path: Option<PathBuf>, description: &str) -> Option<T> {
+    return load_user_file(path, description, |text| {
+        serde_yaml::from_str(text).map_err(|e| e.to_string())
+    });
+}
+
+// Writes a per user file, creating its directory. Failures are logged, as the game carries on
+// with the values it has in memory.
+pub fn save_yaml<T: Serialize>(path: Option<PathBuf>, description: &str, value: &T) {
+    save_user_file(
+        path,
+        description,
+        serde_yaml::to_string(value).map_err(|e| e.to_string()),
+    );
+}
+
+pub fn load_ron<T: DeserializeOwned>(path: Option<PathBuf>, description: &str) -> Option<T> {
+    return load_user_file(path, description, |text| {
+        ron::from_str(text).map_err(|e| e.to_string())
+    });
+}
+
+pub fn save_ron<T: Serialize>(path: Option<PathBuf>, description: &str, value: &T) {
+    save_user_file(
+        path,
+        description,
+        ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())
+            .map_err(|e| e.to_string()),
+    );
+}
+
+fn load_user_file<T>(
+    path: Option<PathBuf>,
+    description: &str,
+    parse: impl FnOnce(&str) -> Result<T, String>,
+) -> Option<T> {
+    let path = path?;
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+        Err(e) => {
+            error!("Could not open {} {:?}: {}", description, path, e);
+            return None;
+        }
+    };
+    match parse(&text) {
+        Ok(value) => return Some(value),
+        Err(e) => {
+            error!("Could not read {} {:?}: {}", description, path, e);
+            return None;
+        }
+    }
+}
+
+fn save_user_file(path: Option<PathBuf>, description: &str, text: Result<String, String>) {
+    let Some(path) = path else {
+        warn!("No directory found to save the {} in.", description);
+        return;
+    };
+    let result = text.and_then(|text| write_creating_dirs(&path, &text).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        error!("Could not save {} {:?}: {}", description, path, e);
+    }
+}
+
+fn write_creating_dirs(path: &Path, text: &str) -> std::io::Result<()> {
+    if let Some(parent_dir) = path.parent() {
+        fs::create_dir_all(parent_dir)?;
+    }
+    return fs::write(path, text);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("fok-config-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        return dir;
+    }
+
+    #[test]
+    fn world_names_follow_the_seed_or_heightmap() {
+        let map_config = load_map_config();
+        let seeded = |seed| MapConfig {
+            seed,
+            heightmap_path: None,
+            ..map_config.clone()
+        };
+        assert_eq!(seeded(7).world_name(), "seed_7");
+        assert_ne!(seeded(7).world_name(), seeded(8).world_name());
+        let imported = MapConfig {
+            heightmap_path: Some("assets/heightmaps/island.png".to_string()),
+            ..seeded(7)
+        };
+        assert_eq!(imported.world_name(), "heightmap_island");
+    }
+
+    #[test]
+    fn user_files_round_trip_and_create_their_directory() {
+        let dir = scratch_dir("round-trip");
+        let values = vec![(1, "one".to_string()), (2, "two".to_string())];
+        save_yaml(
+            Some(dir.join("nested").join("values.yml")),
+            "test values",
+            &values,
+        );
+        save_ron(Some(dir.join("values.ron")), "test values", &values);
+        let from_yaml: Option<Vec<(u32, String)>> =
+            load_yaml(Some(dir.join("nested").join("values.yml")), "test values");
+        let from_ron: Option<Vec<(u32, String)>> =
+            load_ron(Some(dir.join("values.ron")), "test values");
+        assert_eq!(from_yaml.as_ref(), Some(&values));
+        assert_eq!(from_ron.as_ref(), Some(&values));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn unreadable_or_unwritable_user_files_are_skipped() {
+        let dir = scratch_dir("unusable");
+        let missing: Option<u32> = load_yaml(Some(dir.join("missing.yml")), "test value");
+        assert_eq!(missing, None);
+        assert_eq!(load_yaml::<u32>(None, "test value"), None);
+
+        write_creating_dirs(&dir.join("broken.yml"), "[not a number").unwrap();
+        let broken: Option<u32> = load_yaml(Some(dir.join("broken.yml")), "test value");
+        assert_eq!(broken, None);
+
+        // A file where the directory should be makes creating it fail, which must not panic
+        save_yaml(
+            Some(dir.join("broken.yml").join("value.yml")),
+            "test value",
+            &3,
+        );
+        save_yaml(None, "test value", &3);
+        assert!(!dir.join("broken.yml").join("value.yml").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
 }
@@ -0,0 +1,746 @@
+use std::collections::BTreeMap;
+
+use bevy::{
+    input::{
+        gamepad::{GamepadAxisType, GamepadButtonType},
+        mouse::MouseWheel,
+        InputSystem,
+    },
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::config_parser::{load_yaml, save_yaml, user_config_path};
+
+const BINDINGS_FILE: &str = "input_bindings.yml";
+
+// Keys for camera bookmark slots 1 to 9
+const DIGIT_KEYS: [KeyCode; 9] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
+pub struct ControlsPlugin;
+
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(InputBindings::load())
+            .init_resource::<ActionState>()
+            .add_systems(PreUpdate, update_action_state.after(InputSystem))
+            .add_systems(Update, save_input_bindings);
+    }
+}
+
+// Everything the player can do with the camera, movement and map overlay controls. Systems read these
+// through `ActionState` instead of checking keys and buttons directly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InputAction {
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    Orbit,
+    LookLeft,
+    LookRight,
+    LookUp,
+    LookDown,
+    RotateDrag,
+    RotateLeft,
+    RotateRight,
+    ZoomIn,
+    ZoomOut,
+    ToggleCameraMode,
+    StoreBookmark1,
+    StoreBookmark2,
+    StoreBookmark3,
+    StoreBookmark4,
+    StoreBookmark5,
+    StoreBookmark6,
+    StoreBookmark7,
+    StoreBookmark8,
+    StoreBookmark9,
+    RecallBookmark1,
+    RecallBookmark2,
+    RecallBookmark3,
+    RecallBookmark4,
+    RecallBookmark5,
+    RecallBookmark6,
+    RecallBookmark7,
+    RecallBookmark8,
+    RecallBookmark9,
+    AddPathKeyframe,
+    RemovePathKeyframe,
+    TogglePathPlayback,
+    ToggleContourOverlay,
+    ToggleGridOverlay,
+    TogglePoliticalOverlay,
+}
+
+impl InputAction {
+    pub const ALL: [InputAction; 39] = [
+        InputAction::MoveForward,
+        InputAction::MoveBack,
+        InputAction::MoveLeft,
+        InputAction::MoveRight,
+        InputAction::Orbit,
+        InputAction::LookLeft,
+        InputAction::LookRight,
+        InputAction::LookUp,
+        InputAction::LookDown,
+        InputAction::RotateDrag,
+        InputAction::RotateLeft,
+        InputAction::RotateRight,
+        InputAction::ZoomIn,
+        InputAction::ZoomOut,
+        InputAction::ToggleCameraMode,
+        InputAction::StoreBookmark1,
+        InputAction::StoreBookmark2,
+        InputAction::StoreBookmark3,
+        InputAction::StoreBookmark4,
+        InputAction::StoreBookmark5,
+        InputAction::StoreBookmark6,
+        InputAction::StoreBookmark7,
+        InputAction::StoreBookmark8,
+        InputAction::StoreBookmark9,
+        InputAction::RecallBookmark1,
+        InputAction::RecallBookmark2,
+        InputAction::RecallBookmark3,
+        InputAction::RecallBookmark4,
+        InputAction::RecallBookmark5,
+        InputAction::RecallBookmark6,
+        InputAction::RecallBookmark7,
+        InputAction::RecallBookmark8,
+        InputAction::RecallBookmark9,
+        InputAction::AddPathKeyframe,
+        InputAction::RemovePathKeyframe,
+        InputAction::TogglePathPlayback,
+        InputAction::ToggleContourOverlay,
+        InputAction::ToggleGridOverlay,
+        InputAction::TogglePoliticalOverlay,
+    ];
+
+    // Actions for bookmark slots 1 to 9, in order
+    pub const STORE_BOOKMARK: [InputAction; 9] = [
+        InputAction::StoreBookmark1,
+        InputAction::StoreBookmark2,
+        InputAction::StoreBookmark3,
+        InputAction::StoreBookmark4,
+        InputAction::StoreBookmark5,
+        InputAction::StoreBookmark6,
+        InputAction::StoreBookmark7,
+        InputAction::StoreBookmark8,
+        InputAction::StoreBookmark9,
+    ];
+    pub const RECALL_BOOKMARK: [InputAction; 9] = [
+        InputAction::RecallBookmark1,
+        InputAction::RecallBookmark2,
+        InputAction::RecallBookmark3,
+        InputAction::RecallBookmark4,
+        InputAction::RecallBookmark5,
+        InputAction::RecallBookmark6,
+        InputAction::RecallBookmark7,
+        InputAction::RecallBookmark8,
+        InputAction::RecallBookmark9,
+    ];
+
+    fn default_bindings(&self) -> Vec<Binding> {
+        use GamepadAxisType::{LeftStickX, LeftStickY, RightStickX, RightStickY};
+        match self {
+            InputAction::MoveForward => vec![
+                Binding::Key(KeyCode::KeyW),
+                Binding::Key(KeyCode::ArrowUp),
+                Binding::GamepadAxis(LeftStickY, AxisDirection::Positive),
+            ],
+            InputAction::MoveBack => vec![
+                Binding::Key(KeyCode::KeyS),
+                Binding::Key(KeyCode::ArrowDown),
+                Binding::GamepadAxis(LeftStickY, AxisDirection::Negative),
+            ],
+            InputAction::MoveLeft => vec![
+                Binding::Key(KeyCode::KeyA),
+                Binding::Key(KeyCode::ArrowLeft),
+                Binding::GamepadAxis(LeftStickX, AxisDirection::Negative),
+            ],
+            InputAction::MoveRight => vec![
+                Binding::Key(KeyCode::KeyD),
+                Binding::Key(KeyCode::ArrowRight),
+                Binding::GamepadAxis(LeftStickX, AxisDirection::Positive),
+            ],
+            InputAction::Orbit => vec![Binding::Mouse(MouseButton::Right)],
+            InputAction::LookLeft => {
+                vec![Binding::GamepadAxis(RightStickX, AxisDirection::Negative)]
+            }
+            InputAction::LookRight => {
+                vec![Binding::GamepadAxis(RightStickX, AxisDirection::Positive)]
+            }
+            InputAction::LookUp => {
+                vec![Binding::GamepadAxis(RightStickY, AxisDirection::Positive)]
+            }
+            InputAction::LookDown => {
+                vec![Binding::GamepadAxis(RightStickY, AxisDirection::Negative)]
+            }
+            InputAction::RotateDrag => vec![Binding::Mouse(MouseButton::Middle)],
+            InputAction::RotateLeft => vec![
+                Binding::Key(KeyCode::KeyQ),
+                Binding::GamepadButton(GamepadButtonType::LeftTrigger),
+            ],
+            InputAction::RotateRight => vec![
+                Binding::Key(KeyCode::KeyE),
+                Binding::GamepadButton(GamepadButtonType::RightTrigger),
+            ],
+            InputAction::ZoomIn => vec![
+                Binding::WheelUp,
+                Binding::GamepadButton(GamepadButtonType::RightTrigger2),
+            ],
+            InputAction::ZoomOut => vec![
+                Binding::WheelDown,
+                Binding::GamepadButton(GamepadButtonType::LeftTrigger2),
+            ],
+            InputAction::ToggleCameraMode => vec![
+                Binding::Key(KeyCode::Tab),
+                Binding::GamepadButton(GamepadButtonType::Select),
+            ],
+            InputAction::StoreBookmark1
+            | InputAction::StoreBookmark2
+            | InputAction::StoreBookmark3
+            | InputAction::StoreBookmark4
+            | InputAction::StoreBookmark5
+            | InputAction::StoreBookmark6
+            | InputAction::StoreBookmark7
+            | InputAction::StoreBookmark8
+            | InputAction::StoreBookmark9 => {
+                let slot = Self::slot(&InputAction::STORE_BOOKMARK, *self);
+                vec![Binding::KeyWith(Modifier::Control, DIGIT_KEYS[slot])]
+            }
+            InputAction::RecallBookmark1
+            | InputAction::RecallBookmark2
+            | InputAction::RecallBookmark3
+            | InputAction::RecallBookmark4
+            | InputAction::RecallBookmark5
+            | InputAction::RecallBookmark6
+            | InputAction::RecallBookmark7
+            | InputAction::RecallBookmark8
+            | InputAction::RecallBookmark9 => {
+                let slot = Self::slot(&InputAction::RECALL_BOOKMARK, *self);
+                vec![Binding::Key(DIGIT_KEYS[slot])]
+            }
+            InputAction::AddPathKeyframe => vec![Binding::Key(KeyCode::KeyK)],
+            InputAction::RemovePathKeyframe => {
+                vec![Binding::KeyWith(Modifier::Shift, KeyCode::KeyK)]
+            }
+            InputAction::TogglePathPlayback => vec![Binding::Key(KeyCode::KeyP)],
+            InputAction::ToggleContourOverlay => vec![
+                Binding::Key(KeyCode::F6),
+                Binding::GamepadButton(GamepadButtonType::DPadUp),
+            ],
+            InputAction::ToggleGridOverlay => vec![
+                Binding::Key(KeyCode::F7),
+                Binding::GamepadButton(GamepadButtonType::DPadLeft),
+            ],
+            InputAction::TogglePoliticalOverlay => vec![
+                Binding::Key(KeyCode::F8),
+                Binding::GamepadButton(GamepadButtonType::DPadRight),
+            ],
+        }
+    }
+
+    fn slot(actions: &[InputAction; 9], action: InputAction) -> usize {
+        return actions.iter().position(|slot| *slot == action).unwrap_or(0);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AxisDirection {
+    Positive,
+    Negative,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Binding {
+    Key(KeyCode),
+    // A key pressed while a modifier is held. While it is held, the key alone does not also
+    // count for another action.
+    KeyWith(Modifier, KeyCode),
+    Mouse(MouseButton),
+    WheelUp,
+    WheelDown,
+    // Buttons on any connected gamepad. Analog triggers report how far they are pulled.
+    GamepadButton(GamepadButtonType),
+    // One half of a stick axis, so each direction can drive its own action
+    GamepadAxis(GamepadAxisType, AxisDirection),
+}
+
+// Either the left or the right key counts
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Modifier {
+    Control,
+    Shift,
+    Alt,
+}
+
+impl Modifier {
+    fn pressed(&self, keys: &ButtonInput<KeyCode>) -> bool {
+        let modifier_keys = match self {
+            Modifier::Control => [KeyCode::ControlLeft, KeyCode::ControlRight],
+            Modifier::Shift => [KeyCode::ShiftLeft, KeyCode::ShiftRight],
+            Modifier::Alt => [KeyCode::AltLeft, KeyCode::AltRight],
+        };
+        return keys.any_pressed(modifier_keys);
+    }
+}
+
+// Stick and trigger tuning, saved in the bindings file alongside the bindings
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GamepadTuning {
+    // Stick deflection ignored around the centre, from 0 to 1. What is left is rescaled so the
+    // stick still reaches full strength at the edge.
+    pub deadzone: f32,
+    // Multiplies the camera's mouse sensitivity when orbiting with the right stick
+    pub look_sensitivity: f32,
+    // Zoom per second with a trigger fully pulled, in mouse wheel steps
+    pub zoom_speed: f32,
+}
+
+impl Default for GamepadTuning {
+    fn default() -> Self {
+        GamepadTuning {
+            deadzone: 0.15,
+            look_sensitivity: 1.0,
+            zoom_speed: 8.0,
+        }
+    }
+}
+
+impl GamepadTuning {
+    fn apply_deadzone(&self, value: f32) -> f32 {
+        let deadzone = self.deadzone.clamp(0.0, 0.99);
+        return ((value - deadzone) / (1.0 - deadzone)).clamp(0.0, 1.0);
+    }
+}
+
+// Each action can have several bindings, but a binding may only belong to one action.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Resource)]
+#[serde(default)]
+pub struct InputBindings {
+    bindings: BTreeMap<InputAction, Vec<Binding>>,
+    pub gamepad: GamepadTuning,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        InputBindings {
+            bindings: InputAction::ALL
+                .iter()
+                .map(|action| (*action, action.default_bindings()))
+                .collect(),
+            gamepad: GamepadTuning::default(),
+        }
+    }
+}
+
+impl InputBindings {
+    // Actions missing from the file keep their default bindings
+    pub fn load() -> InputBindings {
+        let mut input_bindings = InputBindings::default();
+        let loaded: Option<InputBindings> =
+            load_yaml(user_config_path(BINDINGS_FILE), "input bindings");
+        if let Some(loaded) = loaded {
+            input_bindings.bindings.extend(loaded.bindings);
+            input_bindings.gamepad = loaded.gamepad;
+        }
+        for (binding, actions) in input_bindings.conflicts() {
+            warn!(
+                "{:?} is bound to more than one action: {:?}",
+                binding, actions
+            );
+        }
+        return input_bindings;
+    }
+
+    pub fn save(&self) {
+        save_yaml(user_config_path(BINDINGS_FILE), "input bindings", self);
+    }
+
+    // Returns the action already using the binding instead of binding it twice
+    pub fn bind(&mut self, action: InputAction, binding: Binding) -> Result<(), InputAction> {
+        if let Some(existing) = self.action_for(binding) {
+            if existing == action {
+                return Ok(());
+            }
+            return Err(existing);
+        }
+        self.bindings.entry(action).or_default().push(binding);
+        return Ok(());
+    }
+
+    pub fn unbind(&mut self, action: InputAction, binding: Binding) {
+        if let Some(bindings) = self.bindings.get_mut(&action) {
+            bindings.retain(|existing| *existing != binding);
+        }
+    }
+
+    pub fn action_for(&self, binding: Binding) -> Option<InputAction> {
+        return self
+            .bindings
+            .iter()
+            .find(|(_, bindings)| bindings.contains(&binding))
+            .map(|(action, _)| *action);
+    }
+
+    // Bindings shared by more than one action, which can only happen through the file
+    pub fn conflicts(&self) -> Vec<(Binding, Vec<InputAction>)> {
+        let mut conflicts: Vec<(Binding, Vec<InputAction>)> = Vec::new();
+        for (action, bindings) in self.bindings.iter() {
+            for binding in bindings {
+                match conflicts
+                    .iter_mut()
+                    .find(|(existing, _)| existing == binding)
+                {
+                    Some((_, actions)) => actions.push(*action),
+                    None => conflicts.push((*binding, vec![*action])),
+                }
+            }
+        }
+        conflicts.retain(|(_, actions)| actions.len() > 1);
+        return conflicts;
+    }
+}
+
+// How strongly each action is held this frame, from 0 to 1. Sticks and triggers give values in
+// between. The mouse wheel is kept apart as an impulse because it moves a set distance per step
+// rather than a rate per second.
+#[derive(Resource, Default)]
+pub struct ActionState {
+    values: HashMap<InputAction, f32>,
+    impulses: HashMap<InputAction, f32>,
+    previous: HashMap<InputAction, f32>,
+    // Keys pressed as part of a modifier binding stay claimed until they are released, so
+    // letting go of the modifier first does not set off the key's own action
+    claimed_keys: HashSet<KeyCode>,
+}
+
+impl ActionState {
+    pub fn value(&self, action: InputAction) -> f32 {
+        return self.values.get(&action).copied().unwrap_or(0.0);
+    }
+
+    // Scroll distance this frame
+    pub fn impulse(&self, action: InputAction) -> f32 {
+        return self.impulses.get(&action).copied().unwrap_or(0.0);
+    }
+
+    // Difference between two opposing actions, such as the halves of a stick axis
+    pub fn axis(&self, negative: InputAction, positive: InputAction) -> f32 {
+        return self.value(positive) - self.value(negative);
+    }
+
+    pub fn pressed(&self, action: InputAction) -> bool {
+        return self.value(action) > 0.0 || self.impulse(action) > 0.0;
+    }
+
+    pub fn just_pressed(&self, action: InputAction) -> bool {
+        return self.pressed(action) && self.previous.get(&action).copied().unwrap_or(0.0) <= 0.0;
+    }
+
+    pub fn any_pressed(&self) -> bool {
+        return InputAction::ALL.iter().any(|action| self.pressed(*action));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn update_action_state(
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    mut scroll_evr: EventReader<MouseWheel>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>,
+    gamepad_button_axes: Res<Axis<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    input_bindings: Res<InputBindings>,
+    mut action_state: ResMut<ActionState>,
+) {
+    let scroll: f32 = scroll_evr.read().map(|ev| ev.y).sum();
+    let tuning = input_bindings.gamepad;
+
+    // Strongest reading across every connected gamepad
+    let button_value = |button_type: GamepadButtonType| -> f32 {
+        return gamepads
+            .iter()
+            .map(|gamepad| {
+                let button = GamepadButton::new(gamepad, button_type);
+                match gamepad_button_axes.get(button) {
+                    Some(value) => tuning.apply_deadzone(value),
+                    None => gamepad_buttons.pressed(button) as u8 as f32,
+                }
+            })
+            .fold(0.0, f32::max);
+    };
+    let axis_value = |axis_type: GamepadAxisType, direction: AxisDirection| -> f32 {
+        return gamepads
+            .iter()
+            .filter_map(|gamepad| gamepad_axes.get(GamepadAxis::new(gamepad, axis_type)))
+            .map(|value| match direction {
+                AxisDirection::Positive => tuning.apply_deadzone(value),
+                AxisDirection::Negative => tuning.apply_deadzone(-value),
+            })
+            .fold(0.0, f32::max);
+    };
+
+    let action_state = &mut *action_state;
+    action_state.previous.clear();
+    for action in InputAction::ALL {
+        let was_pressed = action_state.pressed(action);
+        action_state
+            .previous
+            .insert(action, was_pressed as u8 as f32);
+    }
+    action_state.values.clear();
+    action_state.impulses.clear();
+    action_state.claimed_keys.retain(|key| keys.pressed(*key));
+    for binding in input_bindings.bindings.values().flatten() {
+        if let Binding::KeyWith(modifier, key) = binding {
+            if modifier.pressed(&keys) && keys.pressed(*key) {
+                action_state.claimed_keys.insert(*key);
+            }
+        }
+    }
+    for (action, bindings) in input_bindings.bindings.iter() {
+        let mut value: f32 = 0.0;
+        let mut impulse: f32 = 0.0;
+        for binding in bindings {
+            match binding {
+                Binding::Key(key) => {
+                    let pressed = keys.pressed(*key) && !action_state.claimed_keys.contains(key);
+                    value = value.max(pressed as u8 as f32)
+                }
+                Binding::KeyWith(modifier, key) => {
+                    let pressed = modifier.pressed(&keys) && keys.pressed(*key);
+                    value = value.max(pressed as u8 as f32)
+                }
+                Binding::Mouse(button) => value = value.max(mouse.pressed(*button) as u8 as f32),
+                Binding::WheelUp => impulse = impulse.max(scroll),
+                Binding::WheelDown => impulse = impulse.max(-scroll),
+                Binding::GamepadButton(button) => value = value.max(button_value(*button)),
+                Binding::GamepadAxis(axis, direction) => {
+                    value = value.max(axis_value(*axis, *direction))
+                }
+            }
+        }
+        action_state.values.insert(*action, value);
+        action_state.impulses.insert(*action, impulse);
+    }
+}
+
+fn save_input_bindings(input_bindings: Res<InputBindings>) {
+    if input_bindings.is_changed() && !input_bindings.is_added() {
+        input_bindings.save();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::input::{
+        gamepad::{GamepadConnection, GamepadConnectionEvent, GamepadInfo},
+        InputPlugin,
+    };
+
+    use super::*;
+
+    // The controls plugin without loading or saving the user's bindings file
+    fn controls_app(input_bindings: InputBindings) -> App {
+        let mut app = App::new();
+        app.add_plugins(InputPlugin)
+            .insert_resource(input_bindings)
+            .init_resource::<ActionState>()
+            .add_systems(PreUpdate, update_action_state.after(InputSystem));
+        return app;
+    }
+
+    fn connect_gamepad(app: &mut App, id: usize) -> Gamepad {
+        let gamepad = Gamepad::new(id);
+        app.world_mut().send_event(GamepadConnectionEvent::new(
+            gamepad,
+            GamepadConnection::Connected(GamepadInfo {
+                name: format!("Test gamepad {}", id),
+            }),
+        ));
+        app.update();
+        return gamepad;
+    }
+
+    fn set_axis(app: &mut App, gamepad: Gamepad, axis_type: GamepadAxisType, value: f32) {
+        app.world_mut()
+            .resource_mut::<Axis<GamepadAxis>>()
+            .set(GamepadAxis::new(gamepad, axis_type), value);
+    }
+
+    // Analog buttons report how far they are pressed, and digital ones 0 or 1, like gilrs does
+    fn set_button(app: &mut App, gamepad: Gamepad, button_type: GamepadButtonType, value: f32) {
+        let button = GamepadButton::new(gamepad, button_type);
+        app.world_mut()
+            .resource_mut::<Axis<GamepadButton>>()
+            .set(button, value);
+        let mut buttons = app.world_mut().resource_mut::<ButtonInput<GamepadButton>>();
+        if value > 0.0 {
+            buttons.press(button);
+        } else {
+            buttons.release(button);
+        }
+    }
+
+    fn value(app: &App, action: InputAction) -> f32 {
+        return app.world().resource::<ActionState>().value(action);
+    }
+
+    fn assert_near(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-5,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn sticks_are_rescaled_past_the_deadzone() {
+        let mut app = controls_app(InputBindings::default());
+        let gamepad = connect_gamepad(&mut app, 0);
+
+        // Inside the default 0.15 deadzone
+        set_axis(&mut app, gamepad, GamepadAxisType::LeftStickY, 0.1);
+        app.update();
+        assert_eq!(value(&app, InputAction::MoveForward), 0.0);
+        assert_eq!(value(&app, InputAction::MoveBack), 0.0);
+
+        set_axis(&mut app, gamepad, GamepadAxisType::LeftStickY, 0.575);
+        app.update();
+        assert_near(value(&app, InputAction::MoveForward), 0.5);
+        assert_eq!(value(&app, InputAction::MoveBack), 0.0);
+
+        set_axis(&mut app, gamepad, GamepadAxisType::LeftStickY, -1.0);
+        set_axis(&mut app, gamepad, GamepadAxisType::RightStickX, 0.3);
+        app.update();
+        let actions = app.world().resource::<ActionState>();
+        assert_eq!(actions.value(InputAction::MoveForward), 0.0);
+        assert_near(actions.value(InputAction::MoveBack), 1.0);
+        assert_near(actions.value(InputAction::LookRight), 0.15 / 0.85);
+        assert_near(
+            actions.axis(InputAction::LookLeft, InputAction::LookRight),
+            0.15 / 0.85,
+        );
+    }
+
+    #[test]
+    fn deadzone_comes_from_the_tuning() {
+        let mut input_bindings = InputBindings::default();
+        input_bindings.gamepad.deadzone = 0.5;
+        let mut app = controls_app(input_bindings);
+        let gamepad = connect_gamepad(&mut app, 0);
+
+        set_axis(&mut app, gamepad, GamepadAxisType::LeftStickX, -0.45);
+        app.update();
+        assert_eq!(value(&app, InputAction::MoveLeft), 0.0);
+
+        set_axis(&mut app, gamepad, GamepadAxisType::LeftStickX, -0.75);
+        app.update();
+        assert_near(value(&app, InputAction::MoveLeft), 0.5);
+    }
+
+    #[test]
+    fn strongest_gamepad_wins() {
+        let mut app = controls_app(InputBindings::default());
+        let first = connect_gamepad(&mut app, 0);
+        let second = connect_gamepad(&mut app, 1);
+
+        set_axis(&mut app, first, GamepadAxisType::RightStickY, 0.4);
+        set_axis(&mut app, second, GamepadAxisType::RightStickY, 0.66);
+        app.update();
+        assert_near(value(&app, InputAction::LookUp), 0.6);
+    }
+
+    #[test]
+    fn triggers_are_analog_and_buttons_are_pressed() {
+        let mut app = controls_app(InputBindings::default());
+        let gamepad = connect_gamepad(&mut app, 0);
+
+        set_button(&mut app, gamepad, GamepadButtonType::RightTrigger2, 0.575);
+        set_button(&mut app, gamepad, GamepadButtonType::Select, 1.0);
+        app.update();
+        let actions = app.world().resource::<ActionState>();
+        assert_near(actions.value(InputAction::ZoomIn), 0.5);
+        assert_near(actions.axis(InputAction::ZoomOut, InputAction::ZoomIn), 0.5);
+        assert!(actions.just_pressed(InputAction::ToggleCameraMode));
+
+        app.update();
+        let actions = app.world().resource::<ActionState>();
+        assert!(actions.pressed(InputAction::ToggleCameraMode));
+        assert!(!actions.just_pressed(InputAction::ToggleCameraMode));
+
+        set_button(&mut app, gamepad, GamepadButtonType::Select, 0.0);
+        app.update();
+        assert!(!app
+            .world()
+            .resource::<ActionState>()
+            .pressed(InputAction::ToggleCameraMode));
+    }
+
+    #[test]
+    fn modifier_bindings_take_the_key_from_plain_bindings() {
+        let mut app = controls_app(InputBindings::default());
+        let press = |app: &mut App, key: KeyCode| {
+            app.world_mut()
+                .resource_mut::<ButtonInput<KeyCode>>()
+                .press(key);
+        };
+        let release = |app: &mut App, key: KeyCode| {
+            app.world_mut()
+                .resource_mut::<ButtonInput<KeyCode>>()
+                .release(key);
+        };
+
+        press(&mut app, KeyCode::ControlLeft);
+        press(&mut app, KeyCode::Digit3);
+        app.update();
+        let actions = app.world().resource::<ActionState>();
+        assert!(actions.just_pressed(InputAction::StoreBookmark3));
+        assert!(!actions.pressed(InputAction::RecallBookmark3));
+
+        // Letting go of Ctrl before the digit does not recall the slot just stored
+        release(&mut app, KeyCode::ControlLeft);
+        app.update();
+        let actions = app.world().resource::<ActionState>();
+        assert!(!actions.pressed(InputAction::StoreBookmark3));
+        assert!(!actions.pressed(InputAction::RecallBookmark3));
+
+        release(&mut app, KeyCode::Digit3);
+        app.update();
+        press(&mut app, KeyCode::Digit3);
+        app.update();
+        let actions = app.world().resource::<ActionState>();
+        assert!(actions.just_pressed(InputAction::RecallBookmark3));
+        assert!(!actions.pressed(InputAction::StoreBookmark3));
+    }
+
+    #[test]
+    fn current_bindings_round_trip() {
+        let mut input_bindings = InputBindings::default();
+        input_bindings.gamepad.deadzone = 0.3;
+        input_bindings.unbind(InputAction::ZoomIn, Binding::WheelUp);
+        let yaml = serde_yaml::to_string(&input_bindings).unwrap();
+        let loaded: InputBindings = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(loaded, input_bindings);
+    }
+}
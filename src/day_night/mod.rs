@@ -0,0 +1,199 @@
+use std::f32::consts::{PI, TAU};
+
+use bevy::prelude::*;
+
+use crate::config_parser;
+use crate::loading_screen::AppState;
+
+const HOURS_PER_DAY: f32 = 24.0;
+// Tilts the sun's path away from straight east to west so shadows never line up with the grid
+const SUN_AZIMUTH: f32 = PI / 6.0;
+const SUN_ILLUMINANCE: f32 = light_consts::lux::AMBIENT_DAYLIGHT;
+// Far brighter than a real full moon so the map stays readable at night
+const MOON_ILLUMINANCE: f32 = 400.0;
+const DAY_AMBIENT_BRIGHTNESS: f32 = 150.0;
+const NIGHT_AMBIENT_BRIGHTNESS: f32 = 30.0;
+const SUNRISE_KELVIN: f32 = 2000.0;
+const NOON_KELVIN: f32 = 5800.0;
+
+pub struct DayNightPlugin;
+
+impl Plugin for DayNightPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::InGame), spawn_celestial_lights)
+            .add_systems(
+                Update,
+                (advance_time_of_day, update_celestial_lights)
+                    .chain()
+                    .run_if(in_state(AppState::InGame)),
+            );
+    }
+}
+
+// In game clock. `hour` runs from 0 to 24 with the sun rising at 6 and setting at 18, and a
+// full day takes `day_length` real seconds.
+#[derive(Resource)]
+pub struct TimeOfDay {
+    pub hour: f32,
+    pub day: u32,
+    pub day_length: f32,
+}
+
+impl TimeOfDay {
+    // Angle of the sun above the eastern horizon, in radians. Negative at night.
+    fn sun_angle(&self) -> f32 {
+        return (self.hour - 6.0) / HOURS_PER_DAY * TAU;
+    }
+}
+
+#[derive(Component)]
+struct Sun;
+
+#[derive(Component)]
+struct Moon;
+
+fn spawn_celestial_lights(mut commands: Commands, engine_config: Res<config_parser::EngineConfig>) {
+    commands.insert_resource(TimeOfDay {
+        hour: engine_config.start_hour,
+        day: 0,
+        day_length: engine_config.day_length,
+    });
+    commands.spawn((celestial_light(SUN_ILLUMINANCE), Sun));
+    commands.spawn((celestial_light(MOON_ILLUMINANCE), Moon));
+}
+
+fn celestial_light(illuminance: f32) -> DirectionalLightBundle {
+    return DirectionalLightBundle {
+        directional_light: DirectionalLight {
+            illuminance,
+            // Turned on by `update_celestial_lights` for whichever light is up
+            shadows_enabled: false,
+            ..default()
+        },
+        // Cascades are fitted to the world and camera by `graphics::configure_shadow_cascades`
+        ..default()
+    };
+}
+
+fn advance_time_of_day(time: Res<Time>, mut time_of_day: ResMut<TimeOfDay>) {
+    if time_of_day.day_length <= 0.0 {
+        return;
+    }
+    time_of_day.hour += time.delta_seconds() / time_of_day.day_length * HOURS_PER_DAY;
+    while time_of_day.hour >= HOURS_PER_DAY {
+        time_of_day.hour -= HOURS_PER_DAY;
+        time_of_day.day += 1;
+    }
+}
+
+// The sun and moon sit on opposite sides of the sky. Each fades out just below the horizon, and
+// the sun warms towards sunrise and sunset. Only the one above the horizon casts shadows, so a
+// single set of cascades is rendered at any time.
+fn update_celestial_lights(
+    time_of_day: Res<TimeOfDay>,
+    mut ambient_light: ResMut<AmbientLight>,
+    mut sun_q: Query<(&mut DirectionalLight, &mut Transform), (With<Sun>, Without<Moon>)>,
+    mut moon_q: Query<(&mut DirectionalLight, &mut Transform), (With<Moon>, Without<Sun>)>,
+) {
+    let sun_angle = time_of_day.sun_angle();
+    let daylight = horizon_fade(sun_angle.sin());
+    let moonlight = horizon_fade((sun_angle + PI).sin());
+    let sun_is_up = sun_angle.sin() >= 0.0;
+
+    for (mut light, mut transform) in sun_q.iter_mut() {
+        transform.rotation = sky_rotation(sun_angle);
+        light.illuminance = SUN_ILLUMINANCE * daylight;
+        light.shadows_enabled = sun_is_up;
+        light.color = color_temperature(
+            SUNRISE_KELVIN + (NOON_KELVIN - SUNRISE_KELVIN) * sun_angle.sin().max(0.0),
+        );
+    }
+    for (mut light, mut transform) in moon_q.iter_mut() {
+        transform.rotation = sky_rotation(sun_angle + PI);
+        light.illuminance = MOON_ILLUMINANCE * moonlight * (1.0 - daylight);
+        light.shadows_enabled = !sun_is_up;
+        light.color = Color::srgb(0.65, 0.75, 1.0);
+    }
+
+    ambient_light.brightness =
+        NIGHT_AMBIENT_BRIGHTNESS + (DAY_AMBIENT_BRIGHTNESS - NIGHT_AMBIENT_BRIGHTNESS) * daylight;
+    ambient_light.color = Color::srgb(0.55, 0.6, 0.9).mix(&Color::WHITE, daylight);
+}
+
+// Directional lights shine along their forward axis, so tilting forward by the angle above the
+// horizon points the light down from that spot in the sky.
+fn sky_rotation(angle: f32) -> Quat {
+    return Quat::from_rotation_y(SUN_AZIMUTH) * Quat::from_rotation_x(-angle);
+}
+
+fn horizon_fade(height: f32) -> f32 {
+    let t = ((height + 0.05) / 0.3).clamp(0.0, 1.0);
+    return t * t * (3.0 - 2.0 * t);
+}
+
+// Approximation of black body colors between 1000K and 40000K
+fn color_temperature(kelvin: f32) -> Color {
+    let t = kelvin.clamp(1000.0, 40000.0) / 100.0;
+    let red = if t <= 66.0 {
+        255.0
+    } else {
+        329.699 * (t - 60.0).powf(-0.133_205)
+    };
+    let green = if t <= 66.0 {
+        99.470_8 * t.ln() - 161.119_57
+    } else {
+        288.122_17 * (t - 60.0).powf(-0.075_514_85)
+    };
+    let blue = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.517_73 * (t - 10.0).ln() - 305.044_8
+    };
+    return Color::srgb(
+        red.clamp(0.0, 255.0) / 255.0,
+        green.clamp(0.0, 255.0) / 255.0,
+        blue.clamp(0.0, 255.0) / 255.0,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shadow_casters(hour: f32) -> (bool, bool) {
+        let mut app = App::new();
+        app.insert_resource(AmbientLight::default())
+            .insert_resource(TimeOfDay {
+                hour,
+                day: 0,
+                day_length: 0.0,
+            })
+            .add_systems(Update, update_celestial_lights);
+        app.world_mut()
+            .spawn((celestial_light(SUN_ILLUMINANCE), Sun));
+        app.world_mut()
+            .spawn((celestial_light(MOON_ILLUMINANCE), Moon));
+        app.update();
+
+        let world = app.world_mut();
+        let sun = world
+            .query_filtered::<&DirectionalLight, With<Sun>>()
+            .single(world)
+            .shadows_enabled;
+        let moon = world
+            .query_filtered::<&DirectionalLight, With<Moon>>()
+            .single(world)
+            .shadows_enabled;
+        return (sun, moon);
+    }
+
+    #[test]
+    fn only_the_light_above_the_horizon_casts_shadows() {
+        assert_eq!(shadow_casters(12.0), (true, false));
+        assert_eq!(shadow_casters(7.0), (true, false));
+        assert_eq!(shadow_casters(0.0), (false, true));
+        assert_eq!(shadow_casters(19.0), (false, true));
+    }
+}
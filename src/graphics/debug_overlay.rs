@@ -0,0 +1,63 @@
+use bevy::{
+    pbr::wireframe::{WireframeConfig, WireframePlugin},
+    prelude::*,
+};
+use bevy_rapier3d::prelude::{DebugRenderContext, RapierDebugRenderPlugin};
+use iyes_perf_ui::prelude::*;
+
+use crate::graphics::GraphicsSettings;
+
+// Perf UI, global wireframe and physics gizmos. Only built with the `debug_overlays` feature,
+// and the visibility of each overlay is kept in `GraphicsSettings` so it persists.
+pub struct DebugOverlayPlugin;
+
+impl Plugin for DebugOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            bevy::diagnostic::FrameTimeDiagnosticsPlugin,
+            bevy::diagnostic::EntityCountDiagnosticsPlugin,
+            bevy::diagnostic::SystemInformationDiagnosticsPlugin,
+            PerfUiPlugin,
+            WireframePlugin,
+            RapierDebugRenderPlugin::default(),
+        ))
+        .add_systems(
+            Update,
+            (toggle_debug_overlays, apply_debug_overlays).chain(),
+        );
+    }
+}
+
+fn toggle_debug_overlays(keys: Res<ButtonInput<KeyCode>>, mut settings: ResMut<GraphicsSettings>) {
+    if keys.just_pressed(KeyCode::F3) {
+        settings.show_perf_ui = !settings.show_perf_ui;
+    }
+    if keys.just_pressed(KeyCode::F4) {
+        settings.show_wireframe = !settings.show_wireframe;
+    }
+    if keys.just_pressed(KeyCode::F5) {
+        settings.show_physics_debug = !settings.show_physics_debug;
+    }
+}
+
+fn apply_debug_overlays(
+    mut commands: Commands,
+    settings: Res<GraphicsSettings>,
+    mut wireframe_config: ResMut<WireframeConfig>,
+    mut debug_render_context: ResMut<DebugRenderContext>,
+    perf_ui_q: Query<Entity, With<PerfUiRoot>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    wireframe_config.global = settings.show_wireframe;
+    debug_render_context.enabled = settings.show_physics_debug;
+
+    if settings.show_perf_ui && perf_ui_q.is_empty() {
+        commands.spawn(PerfUiCompleteBundle::default());
+    } else if !settings.show_perf_ui {
+        for entity in perf_ui_q.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,119 @@
+#[cfg(feature = "debug_overlays")]
+mod debug_overlay;
+mod settings;
+
+use std::f32::consts::SQRT_2;
+
+use bevy::{
+    pbr::{CascadeShadowConfig, CascadeShadowConfigBuilder, DirectionalLightShadowMap},
+    prelude::*,
+};
+
+use crate::camera_system::ThirdPersonCamera;
+use crate::config_parser::{EngineConfig, ShadowQuality};
+use crate::loading_screen::AppState;
+#[cfg(feature = "debug_overlays")]
+pub use debug_overlay::DebugOverlayPlugin;
+pub use settings::GraphicsSettings;
+
+pub struct GraphicsPlugin;
+
+impl Plugin for GraphicsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                settings::toggle_fullscreen,
+                settings::apply_graphics_settings,
+                settings::save_graphics_settings,
+            )
+                .chain(),
+        )
+        .add_systems(
+            Update,
+            configure_shadow_cascades.run_if(in_state(AppState::InGame)),
+        );
+    }
+}
+
+impl ShadowQuality {
+    fn cascade_count(&self) -> usize {
+        match self {
+            ShadowQuality::Low => 2,
+            ShadowQuality::Medium => 3,
+            ShadowQuality::High => 4,
+        }
+    }
+
+    // How far shadows reach, as a multiple of the furthest camera zoom
+    fn distance_per_zoom(&self) -> f32 {
+        match self {
+            ShadowQuality::Low => 3.0,
+            ShadowQuality::Medium => 6.0,
+            ShadowQuality::High => 12.0,
+        }
+    }
+
+    fn shadow_map_size(&self) -> usize {
+        match self {
+            ShadowQuality::Low => 1024,
+            ShadowQuality::Medium => 2048,
+            ShadowQuality::High => 4096,
+        }
+    }
+}
+
+// The first cascade covers the area around the focus at the middle of the zoom range, and the
+// last one reaches a multiple of the furthest zoom, but never past the far corner of the world.
+fn cascade_shadow_config(
+    engine_config: &EngineConfig,
+    quality: ShadowQuality,
+    zoom_min: f32,
+    zoom_max: f32,
+) -> CascadeShadowConfig {
+    let world_extent = (engine_config.world_size - 1) as f32 * SQRT_2 + engine_config.world_height;
+    let maximum_distance = (zoom_max * quality.distance_per_zoom()).min(world_extent);
+    let first_cascade_far_bound = ((zoom_min + zoom_max) / 2.0).min(maximum_distance);
+    return CascadeShadowConfigBuilder {
+        num_cascades: quality.cascade_count(),
+        first_cascade_far_bound,
+        maximum_distance,
+        ..default()
+    }
+    .into();
+}
+
+fn configure_shadow_cascades(
+    mut commands: Commands,
+    engine_config: Res<EngineConfig>,
+    graphics_settings: Res<GraphicsSettings>,
+    cam_q: Query<Ref<ThirdPersonCamera>>,
+    new_lights: Query<(), Added<DirectionalLight>>,
+    mut light_q: Query<&mut CascadeShadowConfig, With<DirectionalLight>>,
+) {
+    if !engine_config.is_changed()
+        && !graphics_settings.is_changed()
+        && new_lights.is_empty()
+        && !cam_q.iter().any(|cam| cam.is_added())
+    {
+        return;
+    }
+    // Every camera has to be covered, so use the widest zoom range among them
+    let Some((zoom_min, zoom_max)) = cam_q.iter().fold(None, |range, cam| {
+        let (min, max) = range.unwrap_or((cam.zoom.min, cam.zoom.max));
+        Some((min.min(cam.zoom.min), max.max(cam.zoom.max)))
+    }) else {
+        return;
+    };
+
+    let quality = graphics_settings
+        .shadow_quality
+        .unwrap_or(engine_config.shadow_quality);
+    let config = cascade_shadow_config(&engine_config, quality, zoom_min, zoom_max);
+    for mut cascade_shadow_config in light_q.iter_mut() {
+        *cascade_shadow_config = config.clone();
+    }
+    commands.insert_resource(DirectionalLightShadowMap {
+        size: quality.shadow_map_size(),
+    });
+}
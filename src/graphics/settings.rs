@@ -0,0 +1,127 @@
+use bevy::{
+    prelude::*,
+    window::{PresentMode, PrimaryWindow, WindowMode, WindowResolution},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::config_parser::{load_yaml, save_yaml, user_config_path, ShadowQuality};
+
+const WINDOW_TITLE: &str = "Foundations of a Kingdom";
+const SETTINGS_FILE: &str = "graphics_settings.yml";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DisplayMode {
+    Windowed,
+    BorderlessFullscreen,
+    Fullscreen,
+}
+
+// Per user settings, stored in the platform config directory. Missing fields fall back to their
+// defaults.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Resource)]
+#[serde(default)]
+pub struct GraphicsSettings {
+    pub width: f32,
+    pub height: f32,
+    pub vsync: bool,
+    pub display_mode: DisplayMode,
+    // 1, 2, 4 or 8 samples per pixel
+    pub msaa_samples: u32,
+    // Overrides the preset in engine_config.yml when set
+    pub shadow_quality: Option<ShadowQuality>,
+    pub show_perf_ui: bool,
+    pub show_wireframe: bool,
+    pub show_physics_debug: bool,
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        GraphicsSettings {
+            width: 1280.0,
+            height: 720.0,
+            vsync: true,
+            display_mode: DisplayMode::Windowed,
+            msaa_samples: 4,
+            shadow_quality: None,
+            show_perf_ui: cfg!(debug_assertions),
+            show_wireframe: false,
+            show_physics_debug: cfg!(debug_assertions),
+        }
+    }
+}
+
+impl GraphicsSettings {
+    pub fn load() -> GraphicsSettings {
+        return load_yaml(user_config_path(SETTINGS_FILE), "graphics settings").unwrap_or_default();
+    }
+
+    pub fn save(&self) {
+        save_yaml(user_config_path(SETTINGS_FILE), "graphics settings", self);
+    }
+
+    pub fn window(&self) -> Window {
+        let mut window = Window {
+            title: WINDOW_TITLE.into(),
+            ..default()
+        };
+        self.apply_to_window(&mut window);
+        return window;
+    }
+
+    fn apply_to_window(&self, window: &mut Window) {
+        if window.resolution.width() != self.width || window.resolution.height() != self.height {
+            window.resolution = WindowResolution::new(self.width, self.height);
+        }
+        window.present_mode = if self.vsync {
+            PresentMode::AutoVsync
+        } else {
+            PresentMode::AutoNoVsync
+        };
+        window.mode = match self.display_mode {
+            DisplayMode::Windowed => WindowMode::Windowed,
+            DisplayMode::BorderlessFullscreen => WindowMode::BorderlessFullscreen,
+            DisplayMode::Fullscreen => WindowMode::Fullscreen,
+        };
+    }
+
+    pub fn msaa(&self) -> Msaa {
+        match self.msaa_samples {
+            0 | 1 => Msaa::Off,
+            2 => Msaa::Sample2,
+            8 => Msaa::Sample8,
+            _ => Msaa::Sample4,
+        }
+    }
+}
+
+pub fn apply_graphics_settings(
+    mut commands: Commands,
+    settings: Res<GraphicsSettings>,
+    mut window_q: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    if let Ok(mut window) = window_q.get_single_mut() {
+        settings.apply_to_window(&mut window);
+    }
+    commands.insert_resource(settings.msaa());
+}
+
+pub fn save_graphics_settings(settings: Res<GraphicsSettings>) {
+    if settings.is_changed() && !settings.is_added() {
+        settings.save();
+    }
+}
+
+// F11 switches between a window and borderless fullscreen
+pub fn toggle_fullscreen(keys: Res<ButtonInput<KeyCode>>, mut settings: ResMut<GraphicsSettings>) {
+    if !keys.just_pressed(KeyCode::F11) {
+        return;
+    }
+    settings.display_mode = match settings.display_mode {
+        DisplayMode::Windowed => DisplayMode::BorderlessFullscreen,
+        _ => DisplayMode::Windowed,
+    };
+}
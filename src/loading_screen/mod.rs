@@ -1,21 +1,13 @@
-use std::f32::consts::PI;
-
 use bevy::{
-    pbr::{
-        wireframe::Wireframe, CascadeShadowConfigBuilder, ExtendedMaterial, OpaqueRendererMethod,
-    },
+    pbr::{wireframe::Wireframe, ExtendedMaterial, OpaqueRendererMethod},
     prelude::*,
     tasks::{AsyncComputeTaskPool, Task},
 };
 
-use bevy_rapier3d::prelude::*;
-
 use crate::camera_system;
 use crate::config_parser;
 use crate::terrain_generator;
 
-use iyes_perf_ui::prelude::PerfUiCompleteBundle;
-
 use bevy_asset_loader::prelude::*;
 use futures_lite::future;
 use noise::utils::NoiseMap;
@@ -36,7 +28,7 @@ struct LoadingScreenComponent;
 struct ComputeMapComponent(Task<NoiseMap>);
 
 #[derive(Component)]
-struct ComputeMeshComponent(Task<(Vec<Mesh>, Vec<(Vec<Vec3>, Vec<[u32; 3]>)>)>);
+struct ComputeMeshComponent(Task<terrain_generator::TerrainMeshes>);
 
 pub struct LoadingScreenPlugin;
 
@@ -62,8 +54,7 @@ impl Plugin for LoadingScreenPlugin {
                 Update,
                 handle_map_mesh_tasks.run_if(in_state(AppState::GeneratingMeshes)),
             )
-            .add_systems(OnEnter(AppState::InGame), enter_game)
-            .add_systems(Update, player_movement.run_if(in_state(AppState::InGame)));
+            .add_systems(OnEnter(AppState::InGame), enter_game);
     }
 }
 
@@ -80,7 +71,6 @@ fn loading_screen(mut commands: Commands) {
         .with_text_justify(JustifyText::Center),
         LoadingScreenComponent,
     ));
-    commands.spawn(PerfUiCompleteBundle::default());
 }
 
 fn generate_terrain(
@@ -119,15 +109,17 @@ fn handle_map_generation_tasks(
 
 fn mesh_terrain(
     mut commands: Commands,
+    map_config: Res<config_parser::MapConfig>,
     engine_config: Res<config_parser::EngineConfig>,
     height_map: Res<terrain_generator::TerrainMap>,
 ) {
     let thread_pool = AsyncComputeTaskPool::get();
     let e_config = engine_config.clone();
+    let sea_level = map_config.sea_level;
     let map = height_map.map.iter().copied().collect();
     let task = thread_pool.spawn(async move {
-        let (meshes, colliders) = terrain_generator::create_map_mesh(e_config, map).await;
-        return (meshes, colliders);
+        let meshes = terrain_generator::create_map_mesh(e_config, sea_level, map).await;
+        return meshes;
     });
     commands.spawn(()).insert(ComputeMeshComponent(task));
 }
@@ -136,57 +128,73 @@ fn handle_map_mesh_tasks(
     mut commands: Commands,
     mut tasks: Query<(Entity, &mut ComputeMeshComponent)>,
     mut state: ResMut<NextState<AppState>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    /*
-    mut materials: ResMut<
+    mut terrain_materials: ResMut<
         Assets<ExtendedMaterial<StandardMaterial, terrain_generator::TerrainMaterial>>,
     >,
-    */
+    mut water_materials: ResMut<
+        Assets<ExtendedMaterial<StandardMaterial, terrain_generator::WaterMaterial>>,
+    >,
     mut meshes: ResMut<Assets<Mesh>>,
+    map_config: Res<config_parser::MapConfig>,
     engine_config: Res<config_parser::EngineConfig>,
+    map_overlay: Res<terrain_generator::MapOverlay>,
 ) {
     for (entity, mut task_component) in tasks.iter_mut() {
         let future = future::block_on(future::poll_once(&mut task_component.0));
-        if let Some((map_meshes, colliders)) = future {
-            let scale_factor = (engine_config.world_size / engine_config.chunk_size) + 1;
-            for x in 0..scale_factor {
-                for z in 0..scale_factor {
-                    let index = (x * scale_factor) + z;
-                    commands.spawn((
+        if let Some(terrain_meshes) = future {
+            let mut terrain_extension =
+                terrain_generator::TerrainMaterial::new(&map_config, &engine_config);
+            map_overlay.apply(&mut terrain_extension);
+            let terrain_material = terrain_materials.add(ExtendedMaterial {
+                base: StandardMaterial {
+                    opaque_render_method: OpaqueRendererMethod::Auto,
+                    metallic: 0.0,
+                    reflectance: 0.0,
+                    perceptual_roughness: 1.0,
+                    ..Default::default()
+                },
+                extension: terrain_extension,
+            });
+            let water_material = water_materials.add(ExtendedMaterial {
+                base: StandardMaterial {
+                    alpha_mode: AlphaMode::Blend,
+                    metallic: 0.0,
+                    reflectance: 0.5,
+                    perceptual_roughness: 0.1,
+                    ..Default::default()
+                },
+                extension: terrain_generator::WaterMaterial::default(),
+            });
+            for chunk in terrain_generator::ChunkCoord::iter(&engine_config) {
+                let index = chunk.index(&engine_config);
+                let (collider, collider_transform) = terrain_meshes.colliders[index].clone();
+                if let Some(water_mesh) = &terrain_meshes.water[index] {
+                    commands.spawn(MaterialMeshBundle {
+                        mesh: meshes.add(water_mesh.clone()),
+                        material: water_material.clone(),
+                        transform: Transform::from_translation(chunk.world_origin(&engine_config)),
+                        ..default()
+                    });
+                }
+                commands
+                    .spawn((
                         MaterialMeshBundle {
-                            mesh: meshes.add(map_meshes[index].clone()),
-                            material: materials.add(StandardMaterial {
-                                base_color: Color::srgb_u8(255, 255, 255),
-                                opaque_render_method: OpaqueRendererMethod::Auto,
-                                metallic: 0.0,
-                                reflectance: 0.0,
-                                perceptual_roughness: 1.0,
-                                ..Default::default()
-                            }),
-                            /*
-                            material: materials.add(ExtendedMaterial {
-                                base: StandardMaterial {
-                                    base_color: Color::srgb_u8(124, 144, 255),
-                                    opaque_render_method: OpaqueRendererMethod::Auto,
-                                    metallic: 0.0,
-                                    reflectance: 0.0,
-                                    perceptual_roughness: 1.0,
-                                    ..Default::default()
-                                },
-                                extension: terrain_generator::TerrainMaterial {},
-                            }),
-                            */
-                            transform: Transform::from_xyz(
-                                x as f32 * (engine_config.chunk_size as f32 - 1.0),
-                                0.0,
-                                z as f32 * (engine_config.chunk_size as f32 - 1.0),
+                            mesh: meshes.add(terrain_meshes.terrain[index].clone()),
+                            material: terrain_material.clone(),
+                            transform: Transform::from_translation(
+                                chunk.world_origin(&engine_config),
                             ),
                             ..default()
                         },
-                        //Collider::trimesh(colliders[index].0.clone(), colliders[index].1.clone()),
                         //Wireframe,
-                    ));
-                }
+                        chunk,
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn((
+                            collider,
+                            TransformBundle::from_transform(collider_transform),
+                        ));
+                    });
             }
             commands.entity(entity).remove::<ComputeMeshComponent>();
             info!(
@@ -198,88 +206,12 @@ fn handle_map_mesh_tasks(
     }
 }
 
-fn enter_game(
-    mut commands: Commands,
-    loading_query: Query<Entity, With<LoadingScreenComponent>>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-) {
+fn enter_game(mut commands: Commands, loading_query: Query<Entity, With<LoadingScreenComponent>>) {
     for loading_component in loading_query.iter() {
         commands.entity(loading_component).despawn();
     }
-    commands.spawn(DirectionalLightBundle {
-        directional_light: DirectionalLight {
-            illuminance: light_consts::lux::AMBIENT_DAYLIGHT,
-            shadows_enabled: true,
-            ..default()
-        },
-        transform: Transform {
-            translation: Vec3::new(0.0, 2.0, 0.0),
-            rotation: Quat::from_rotation_x(-PI / 4.),
-            ..default()
-        },
-        // The default cascade config is designed to handle large scenes.
-        // As this example has a much smaller world, we can tighten the shadow
-        // bounds for better visual quality.
-        cascade_shadow_config: CascadeShadowConfigBuilder {
-            first_cascade_far_bound: 4.0,
-            maximum_distance: 30.0,
-            ..default()
-        }
-        .into(),
-        ..default()
-    });
     commands.spawn((
         camera_system::ThirdPersonCamera::default(),
         Camera3dBundle::default(),
     ));
-    commands.spawn((
-        PbrBundle {
-            mesh: meshes.add(Cuboid::new(1.0, 1.0, 1.0)),
-            material: materials.add(Color::srgb_u8(244, 90, 90)),
-            transform: Transform::from_xyz(0.0, 0.5, 0.0),
-            ..default()
-        },
-        camera_system::ThirdPersonCameraTarget,
-    ));
-}
-
-fn player_movement(
-    time: Res<Time>,
-    keys: Res<ButtonInput<KeyCode>>,
-    mut player_q: Query<&mut Transform, With<camera_system::ThirdPersonCameraTarget>>,
-    cam_q: Query<
-        &Transform,
-        (
-            With<Camera3d>,
-            Without<camera_system::ThirdPersonCameraTarget>,
-        ),
-    >,
-) {
-    for mut player_transform in player_q.iter_mut() {
-        let cam = match cam_q.get_single() {
-            Ok(c) => c,
-            Err(e) => Err(format!("Error retrieving camera: {}", e)).unwrap(),
-        };
-
-        let mut direction = Vec3::ZERO;
-        if keys.pressed(KeyCode::KeyW) {
-            direction += *cam.forward();
-        }
-        if keys.pressed(KeyCode::KeyS) {
-            direction += *cam.back();
-        }
-        if keys.pressed(KeyCode::KeyA) {
-            direction += *cam.left();
-        }
-        if keys.pressed(KeyCode::KeyD) {
-            direction += *cam.right();
-        }
-        direction.y = 0.0;
-        let movement = direction.normalize_or_zero() * 4.5 * time.delta_seconds();
-        player_transform.translation += movement;
-        if direction.length_squared() > 0.0 {
-            player_transform.look_to(direction, Vec3::Y);
-        }
-    }
 }
@@ -1,41 +1,72 @@
 mod camera_system;
 mod config_parser;
+mod controls;
+mod day_night;
+mod graphics;
 mod loading_screen;
+mod minimap;
+mod picking;
+mod player;
 mod terrain_generator;
 
-use bevy::{
-    pbr::{wireframe::WireframePlugin, ExtendedMaterial},
-    prelude::*,
-};
+use bevy::{pbr::ExtendedMaterial, prelude::*};
 use bevy_rapier3d::prelude::*;
 use camera_system::ThirdPersonCameraPlugin;
-use iyes_perf_ui::prelude::*;
-use terrain_generator::TerrainMaterial;
+use terrain_generator::{
+    ExportFormat, LayerDebugPlugin, MapOverlayPlugin, TerrainExportPlugin, TerrainMaterial,
+    WaterMaterial,
+};
 
 fn main() {
-    App::new()
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(format) = args
+        .iter()
+        .find_map(|arg| arg.strip_prefix("--export-terrain="))
+    {
+        let format = ExportFormat::parse(format).expect("Export format must be glb or obj.");
+        let per_chunk = args.iter().any(|arg| arg == "--per-chunk");
+        if let Err(e) = terrain_generator::export_headless(format, per_chunk) {
+            eprintln!("Failed to export terrain: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if args.iter().any(|arg| arg == "--dump-layers") {
+        terrain_generator::dump_layers_headless();
+        return;
+    }
+
+    let graphics_settings = graphics::GraphicsSettings::load();
+
+    let mut app = App::new();
+    app.insert_resource(graphics_settings.msaa())
         .add_plugins((
             DefaultPlugins.set(WindowPlugin {
-                primary_window: Some(Window {
-                    resolution: (1280.0, 720.0).into(),
-                    title: "Foundations of a Kingdom".into(),
-                    ..default()
-                }),
+                primary_window: Some(graphics_settings.window()),
                 ..default()
             }),
             //Physics
             RapierPhysicsPlugin::<NoUserData>::default(),
-            // Debug Systems
-            bevy::diagnostic::FrameTimeDiagnosticsPlugin,
-            bevy::diagnostic::EntityCountDiagnosticsPlugin,
-            bevy::diagnostic::SystemInformationDiagnosticsPlugin,
-            PerfUiPlugin,
-            WireframePlugin,
-            RapierDebugRenderPlugin::default(),
+        ))
+        .insert_resource(graphics_settings)
+        .add_plugins((
             //Game logic
             loading_screen::LoadingScreenPlugin,
+            controls::ControlsPlugin,
             ThirdPersonCameraPlugin,
+            player::PlayerPlugin,
+            day_night::DayNightPlugin,
+            graphics::GraphicsPlugin,
+            TerrainExportPlugin,
+            MapOverlayPlugin,
+            LayerDebugPlugin,
+            picking::PickingPlugin,
+            minimap::MinimapPlugin,
             MaterialPlugin::<ExtendedMaterial<StandardMaterial, TerrainMaterial>>::default(),
-        ))
-        .run();
+            MaterialPlugin::<ExtendedMaterial<StandardMaterial, WaterMaterial>>::default(),
+        ));
+    // Debug Systems
+    #[cfg(feature = "debug_overlays")]
+    app.add_plugins(graphics::DebugOverlayPlugin);
+    app.run();
 }
@@ -0,0 +1,207 @@
+use bevy::{prelude::*, ui::RelativeCursorPosition};
+
+use crate::camera_system::{
+    CameraTarget, FocusCameraEvent, LastInputCamera, ThirdPersonCamera, ThirdPersonCameraTarget,
+};
+use crate::config_parser::{EngineConfig, MapConfig};
+use crate::loading_screen::AppState;
+use crate::terrain_generator::{TerrainMap, TerrainQuery};
+
+const MINIMAP_SIZE: f32 = 200.0;
+const PLAYER_MARKER_SIZE: f32 = 6.0;
+const FRUSTUM_LINE_WIDTH: f32 = 1.5;
+
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::InGame), spawn_minimap)
+            .add_systems(
+                Update,
+                (click_minimap, update_player_marker, update_frustum_outline)
+                    .run_if(in_state(AppState::InGame)),
+            );
+    }
+}
+
+// The map is drawn with z = 0 along the top edge, so the third person camera's default view
+// (towards -z) points up on the minimap
+#[derive(Component)]
+struct MinimapComponent;
+
+#[derive(Component)]
+struct PlayerMarker;
+
+// One side of the ground area the camera sees, numbered clockwise from the top left corner of
+// the screen
+#[derive(Component)]
+struct FrustumEdge(usize);
+
+fn spawn_minimap(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    terrain_map: Res<TerrainMap>,
+    map_config: Res<MapConfig>,
+) {
+    let image = images.add(terrain_map.preview_image(&map_config));
+    commands
+        .spawn((
+            ImageBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    right: Val::Px(10.0),
+                    top: Val::Px(10.0),
+                    width: Val::Px(MINIMAP_SIZE),
+                    height: Val::Px(MINIMAP_SIZE),
+                    overflow: Overflow::clip(),
+                    ..default()
+                },
+                image: UiImage::new(image),
+                ..default()
+            },
+            Interaction::default(),
+            RelativeCursorPosition::default(),
+            MinimapComponent,
+        ))
+        .with_children(|parent| {
+            for index in 0..4 {
+                parent.spawn((
+                    NodeBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            height: Val::Px(FRUSTUM_LINE_WIDTH),
+                            ..default()
+                        },
+                        background_color: Color::WHITE.into(),
+                        ..default()
+                    },
+                    FrustumEdge(index),
+                ));
+            }
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        width: Val::Px(PLAYER_MARKER_SIZE),
+                        height: Val::Px(PLAYER_MARKER_SIZE),
+                        ..default()
+                    },
+                    background_color: Color::srgb_u8(244, 90, 90).into(),
+                    ..default()
+                },
+                PlayerMarker,
+            ));
+        });
+}
+
+fn to_minimap(position: Vec3, engine_config: &EngineConfig) -> Vec2 {
+    let cells = (engine_config.world_size - 1) as f32;
+    return Vec2::new(position.x, position.z) / cells * MINIMAP_SIZE;
+}
+
+// The minimap shows the view of the camera that took input last, and clicking a spot on it flies
+// that camera there
+fn click_minimap(
+    mouse: Res<ButtonInput<MouseButton>>,
+    last_input_camera: Res<LastInputCamera>,
+    terrain: TerrainQuery,
+    engine_config: Res<EngineConfig>,
+    minimap_q: Query<&RelativeCursorPosition, With<MinimapComponent>>,
+    mut focus_evw: EventWriter<FocusCameraEvent>,
+) {
+    if !mouse.just_pressed(MouseButton::Left) {
+        return;
+    }
+    let Some(camera) = last_input_camera.0 else {
+        return;
+    };
+    for cursor in minimap_q.iter() {
+        let Some(normalized) = cursor.normalized.filter(|_| cursor.mouse_over()) else {
+            continue;
+        };
+        let cells = (engine_config.world_size - 1) as f32;
+        let (x, z) = (normalized.x * cells, normalized.y * cells);
+        let height = terrain
+            .height_at(x, z)
+            .map_or(terrain.sea_level(), |height| {
+                height.max(terrain.sea_level())
+            });
+        focus_evw.send(FocusCameraEvent {
+            camera,
+            point: Vec3::new(x, height, z),
+        });
+    }
+}
+
+fn update_player_marker(
+    engine_config: Res<EngineConfig>,
+    last_input_camera: Res<LastInputCamera>,
+    cam_q: Query<&CameraTarget, With<ThirdPersonCamera>>,
+    target_q: Query<&Transform, With<ThirdPersonCameraTarget>>,
+    mut marker_q: Query<&mut Style, With<PlayerMarker>>,
+) {
+    let target = last_input_camera
+        .0
+        .and_then(|camera| cam_q.get(camera).ok())
+        .and_then(|target| target_q.get(target.0).ok());
+    for mut style in marker_q.iter_mut() {
+        let Some(target) = target else {
+            style.display = Display::None;
+            continue;
+        };
+        let position = to_minimap(target.translation, &engine_config);
+        style.display = Display::Flex;
+        style.left = Val::Px(position.x - PLAYER_MARKER_SIZE / 2.0);
+        style.top = Val::Px(position.y - PLAYER_MARKER_SIZE / 2.0);
+    }
+}
+
+// Projects the screen corners onto the sea level plane and joins them up. Corners above the
+// horizon are cut off at the far side of the map.
+fn update_frustum_outline(
+    terrain: TerrainQuery,
+    engine_config: Res<EngineConfig>,
+    last_input_camera: Res<LastInputCamera>,
+    cam_q: Query<(&Camera, &GlobalTransform), With<ThirdPersonCamera>>,
+    mut edge_q: Query<(&FrustumEdge, &mut Style, &mut Transform)>,
+) {
+    let Some(Ok((camera, cam_transform))) = last_input_camera.0.map(|camera| cam_q.get(camera))
+    else {
+        return;
+    };
+    let Some(viewport) = camera.logical_viewport_size() else {
+        return;
+    };
+    let max_distance = (engine_config.world_size - 1) as f32 * std::f32::consts::SQRT_2;
+    let ground = terrain.sea_level();
+    let corners = [
+        Vec2::ZERO,
+        Vec2::new(viewport.x, 0.0),
+        viewport,
+        Vec2::new(0.0, viewport.y),
+    ]
+    .map(|corner| {
+        let ray = camera.viewport_to_world(cam_transform, corner)?;
+        let distance = if ray.direction.y < -f32::EPSILON {
+            ((ground - ray.origin.y) / ray.direction.y).clamp(0.0, max_distance)
+        } else {
+            max_distance
+        };
+        return Some(to_minimap(ray.get_point(distance), &engine_config));
+    });
+
+    for (edge, mut style, mut transform) in edge_q.iter_mut() {
+        let (Some(start), Some(end)) = (corners[edge.0], corners[(edge.0 + 1) % 4]) else {
+            style.display = Display::None;
+            continue;
+        };
+        let middle = (start + end) / 2.0;
+        let length = start.distance(end);
+        style.display = Display::Flex;
+        style.width = Val::Px(length);
+        style.left = Val::Px(middle.x - length / 2.0);
+        style.top = Val::Px(middle.y - FRUSTUM_LINE_WIDTH / 2.0);
+        // Layout only sets the translation of a node, so the rotation is kept
+        transform.rotation = Quat::from_rotation_z((end.y - start.y).atan2(end.x - start.x));
+    }
+}
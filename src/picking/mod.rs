@@ -0,0 +1,356 @@
+use bevy::{
+    prelude::*,
+    render::primitives::Aabb,
+    transform::TransformSystem,
+    window::{CursorGrabMode, PrimaryWindow},
+};
+use bevy_rapier3d::prelude::{QueryFilter, RapierContext};
+
+use crate::loading_screen::AppState::InGame;
+use crate::terrain_generator::{ChunkCoord, TerrainQuery};
+
+// Furthest a ray from the cursor looks for a hit, in world units
+const PICK_DISTANCE: f32 = 2000.0;
+// Step used to march the ray over the heightmap when no collider was hit
+const MARCH_STEP: f32 = 0.5;
+// A press and release further apart than this, in pixels, was a drag and not a click
+const CLICK_TOLERANCE: f32 = 4.0;
+const HIGHLIGHT_COLOR: Color = Color::srgb(1.0, 0.85, 0.2);
+
+pub struct PickingPlugin;
+
+impl Plugin for PickingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Hovered>()
+            .add_event::<PickEvent>()
+            .add_systems(
+                PostUpdate,
+                (update_hover, send_pick_events, draw_hover_highlight)
+                    .chain()
+                    .after(TransformSystem::TransformPropagate)
+                    .run_if(in_state(InGame)),
+            );
+    }
+}
+
+// Entities that can be hovered and clicked. Terrain is always pickable.
+#[derive(Component, Default)]
+pub struct Selectable;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PickHit {
+    pub position: Vec3,
+    // Heightmap cell and chunk under the hit, None off the map
+    pub cell: Option<(usize, usize)>,
+    pub chunk: Option<ChunkCoord>,
+    // Selectable entity that was hit, None when the ray hit the terrain
+    pub entity: Option<Entity>,
+}
+
+// Sent when a mouse button is clicked over the world without dragging
+#[derive(Event, Clone, Copy, Debug)]
+pub struct PickEvent {
+    pub hit: PickHit,
+    pub button: MouseButton,
+}
+
+// What is under the cursor this frame
+#[derive(Resource, Default)]
+pub struct Hovered(pub Option<PickHit>);
+
+#[allow(clippy::too_many_arguments)]
+fn update_hover(
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    cam_q: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    rapier_context: Res<RapierContext>,
+    terrain: TerrainQuery,
+    selectable_q: Query<(), With<Selectable>>,
+    parent_q: Query<&Parent>,
+    ui_q: Query<&Interaction>,
+    mut hovered: ResMut<Hovered>,
+) {
+    hovered.0 = None;
+    // The cursor is over a panel such as the minimap, not the world
+    if ui_q
+        .iter()
+        .any(|interaction| *interaction != Interaction::None)
+    {
+        return;
+    }
+    let Ok(window) = window_q.get_single() else {
+        return;
+    };
+    if window.cursor.grab_mode == CursorGrabMode::Locked {
+        return;
+    }
+    let Some(cursor) = window.cursor_position() else {
+        return;
+    };
+    let Some(ray) = cursor_ray(&cam_q, cursor) else {
+        return;
+    };
+
+    let collider_hit = rapier_context.cast_ray(
+        ray.origin,
+        *ray.direction,
+        PICK_DISTANCE,
+        true,
+        QueryFilter::new(),
+    );
+    let (position, entity) = match collider_hit {
+        Some((entity, distance)) => {
+            // Terrain colliders sit on children of the chunk meshes, so walk up to the
+            // entity that carries `Selectable`
+            let selectable = std::iter::once(entity)
+                .chain(parent_q.iter_ancestors(entity))
+                .find(|entity| selectable_q.contains(*entity));
+            (ray.get_point(distance), selectable)
+        }
+        None => match march_terrain(&terrain, ray) {
+            Some(position) => (position, None),
+            None => return,
+        },
+    };
+    hovered.0 = Some(pick_hit(&terrain, position, entity));
+}
+
+fn pick_hit(terrain: &TerrainQuery, position: Vec3, entity: Option<Entity>) -> PickHit {
+    return PickHit {
+        position,
+        cell: terrain.cell_at(position),
+        chunk: terrain.chunk_at(position),
+        entity,
+    };
+}
+
+// Ray through the cursor from the frontmost 3d camera whose viewport contains it, so extra
+// views drawn over part of the window take the click
+fn cursor_ray(
+    cam_q: &Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    cursor: Vec2,
+) -> Option<Ray3d> {
+    return cam_q
+        .iter()
+        .filter(|(camera, _)| camera.is_active)
+        .filter(|(camera, _)| {
+            camera
+                .logical_viewport_rect()
+                .map_or(false, |rect| rect.contains(cursor))
+        })
+        .max_by_key(|(camera, _)| camera.order)
+        .and_then(|(camera, transform)| camera.viewport_to_world(transform, cursor));
+}
+
+// Walks the ray until it drops below the heightmap, then narrows down the crossing
+fn march_terrain(terrain: &TerrainQuery, ray: Ray3d) -> Option<Vec3> {
+    let below = |distance: f32| -> Option<bool> {
+        let point = ray.get_point(distance);
+        return terrain
+            .height_at(point.x, point.z)
+            .map(|height| point.y <= height);
+    };
+    let mut previous = 0.0;
+    let mut distance = MARCH_STEP;
+    while distance <= PICK_DISTANCE {
+        if below(distance) == Some(true) {
+            let (mut above_at, mut below_at) = (previous, distance);
+            for _ in 0..8 {
+                let middle = (above_at + below_at) / 2.0;
+                if below(middle) == Some(true) {
+                    below_at = middle;
+                } else {
+                    above_at = middle;
+                }
+            }
+            return Some(ray.get_point(below_at));
+        }
+        previous = distance;
+        distance += MARCH_STEP;
+    }
+    return None;
+}
+
+fn send_pick_events(
+    mouse: Res<ButtonInput<MouseButton>>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    hovered: Res<Hovered>,
+    mut press_positions: Local<Vec<(MouseButton, Vec2)>>,
+    mut pick_evw: EventWriter<PickEvent>,
+) {
+    let Some(cursor) = window_q
+        .get_single()
+        .ok()
+        .and_then(|window| window.cursor_position())
+    else {
+        return;
+    };
+    for button in mouse.get_just_pressed() {
+        press_positions.retain(|(pressed, _)| pressed != button);
+        press_positions.push((*button, cursor));
+    }
+    for button in mouse.get_just_released() {
+        let Some(index) = press_positions
+            .iter()
+            .position(|(pressed, _)| pressed == button)
+        else {
+            continue;
+        };
+        let (_, pressed_at) = press_positions.swap_remove(index);
+        if pressed_at.distance(cursor) > CLICK_TOLERANCE {
+            continue;
+        }
+        if let Some(hit) = hovered.0 {
+            pick_evw.send(PickEvent {
+                hit,
+                button: *button,
+            });
+        }
+    }
+}
+
+// Outlines the hovered entity's bounds, or the hovered heightmap cell on bare terrain
+fn draw_hover_highlight(
+    mut gizmos: Gizmos,
+    hovered: Res<Hovered>,
+    terrain: TerrainQuery,
+    bounds_q: Query<(&GlobalTransform, Option<&Aabb>)>,
+) {
+    let Some(hit) = hovered.0 else {
+        return;
+    };
+    if let Some(entity) = hit.entity {
+        if let Ok((transform, aabb)) = bounds_q.get(entity) {
+            let (center, size) = match aabb {
+                Some(aabb) => (Vec3::from(aabb.center), Vec3::from(aabb.half_extents) * 2.0),
+                None => (Vec3::ZERO, Vec3::ONE),
+            };
+            let (scale, rotation, translation) = transform.to_scale_rotation_translation();
+            gizmos.cuboid(
+                Transform {
+                    translation: translation + rotation * (center * scale),
+                    rotation,
+                    scale: size * scale * 1.05,
+                },
+                HIGHLIGHT_COLOR,
+            );
+            return;
+        }
+    }
+    let Some((x, z)) = hit.cell else {
+        return;
+    };
+    let corners = [(0, 0), (1, 0), (1, 1), (0, 1), (0, 0)].map(|(dx, dz)| {
+        let (x, z) = ((x + dx) as f32, (z + dz) as f32);
+        let height = terrain.height_at(x, z).unwrap_or(hit.position.y);
+        return Vec3::new(x, height + 0.02, z);
+    });
+    gizmos.linestrip(corners, HIGHLIGHT_COLOR);
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        ecs::system::SystemState,
+        input::{mouse::MouseButtonInput, ButtonState, InputPlugin},
+    };
+    use noise::utils::NoiseMap;
+
+    use super::*;
+    use crate::config_parser::{load_engine_config, load_map_config, EngineConfig, MapConfig};
+    use crate::terrain_generator::TerrainMap;
+
+    const WORLD_SIZE: usize = 33;
+    const WORLD_HEIGHT: f32 = 10.0;
+
+    // Terrain rising along x, 0.25 world units for every unit across
+    fn insert_terrain(world: &mut World) {
+        let mut map = NoiseMap::new(WORLD_SIZE, WORLD_SIZE);
+        for x in 0..WORLD_SIZE {
+            for z in 0..WORLD_SIZE {
+                map.set_value(x, z, x as f64 * 0.025);
+            }
+        }
+        world.insert_resource(TerrainMap { map });
+        world.insert_resource(EngineConfig {
+            world_size: WORLD_SIZE,
+            chunk_size: 8,
+            world_height: WORLD_HEIGHT,
+            ..load_engine_config()
+        });
+        world.insert_resource(MapConfig {
+            sea_level: 0.0,
+            ..load_map_config()
+        });
+    }
+
+    #[test]
+    fn march_terrain_stops_on_the_heightmap() {
+        let mut world = World::new();
+        insert_terrain(&mut world);
+        let mut terrain_state: SystemState<TerrainQuery> = SystemState::new(&mut world);
+        let terrain = terrain_state.get(&world);
+
+        let origin = Vec3::new(2.0, 30.0, 16.0);
+        let direction = Vec3::new(1.0, -1.0, 0.2).normalize();
+        let ray = Ray3d::new(origin, direction);
+        let hit = march_terrain(&terrain, ray).unwrap();
+        let height = terrain.height_at(hit.x, hit.z).unwrap();
+        assert!((hit.y - height).abs() < 0.01, "{} above {}", hit.y, height);
+        // The surface is the plane y = x / 4, so the ray meets it where the two agree
+        let distance = (origin.y - origin.x / 4.0) / (direction.x / 4.0 - direction.y);
+        assert!(hit.distance(ray.get_point(distance)) < 0.01);
+
+        // Looking up, or out over the edge of the map, there is nothing to hit
+        let up = Ray3d::new(origin, Vec3::new(0.3, 1.0, 0.0).normalize());
+        assert_eq!(march_terrain(&terrain, up), None);
+        let away = Ray3d::new(Vec3::new(0.0, 30.0, 16.0), Vec3::new(-1.0, -0.1, 0.0));
+        assert_eq!(march_terrain(&terrain, away), None);
+    }
+
+    #[test]
+    fn clicks_report_the_cell_and_chunk() {
+        let mut app = App::new();
+        app.add_plugins(InputPlugin)
+            .init_resource::<Hovered>()
+            .add_event::<PickEvent>()
+            .add_systems(Update, send_pick_events);
+        insert_terrain(app.world_mut());
+        let mut window = Window::default();
+        window.set_cursor_position(Some(Vec2::new(200.0, 150.0)));
+        let window = app.world_mut().spawn((window, PrimaryWindow)).id();
+
+        let last = (WORLD_SIZE - 1) as f32;
+        let positions = [
+            Vec3::new(3.5, 0.0, 9.25),
+            Vec3::new(last, 0.0, 3.5),
+            Vec3::new(last, 0.0, last),
+        ];
+        let mut terrain_state: SystemState<TerrainQuery> = SystemState::new(app.world_mut());
+        let hits = positions.map(|position| {
+            let terrain = terrain_state.get(app.world());
+            return pick_hit(&terrain, position, None);
+        });
+        assert_eq!(hits[0].cell, Some((3, 9)));
+        assert_eq!(hits[0].chunk, Some(ChunkCoord::new(0, 1)));
+        // The far edges belong to the last cell and chunk
+        assert_eq!(hits[1].cell, Some((WORLD_SIZE - 2, 3)));
+        assert_eq!(hits[2].cell, Some((WORLD_SIZE - 2, WORLD_SIZE - 2)));
+        assert_eq!(hits[2].chunk, Some(ChunkCoord::new(4, 4)));
+
+        let mut picked = Vec::new();
+        for hit in hits {
+            app.world_mut().resource_mut::<Hovered>().0 = Some(hit);
+            for state in [ButtonState::Pressed, ButtonState::Released] {
+                app.world_mut().send_event(MouseButtonInput {
+                    button: MouseButton::Left,
+                    state,
+                    window,
+                });
+                app.update();
+            }
+            let events = app.world().resource::<Events<PickEvent>>();
+            picked.extend(events.get_reader().read(events).map(|ev| ev.hit));
+        }
+        assert_eq!(picked, hits.to_vec());
+    }
+}
@@ -0,0 +1,295 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::camera_system;
+use crate::config_parser;
+use crate::controls::{ActionState, InputAction};
+use crate::loading_screen::AppState;
+use crate::picking;
+use crate::terrain_generator;
+
+const PLAYER_HALF_HEIGHT: f32 = 0.5;
+
+pub struct PlayerPlugin;
+
+impl Plugin for PlayerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::InGame), spawn_player)
+            .add_systems(Update, player_movement.run_if(in_state(AppState::InGame)));
+    }
+}
+
+#[derive(Component, Default)]
+pub struct Player {
+    pub vertical_velocity: f32,
+}
+
+fn spawn_player(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    terrain: terrain_generator::TerrainQuery,
+    player_config: Res<config_parser::PlayerConfig>,
+) {
+    let ground = terrain.height_at(0.0, 0.0).unwrap_or(0.0);
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(Cuboid::new(1.0, 1.0, 1.0)),
+            material: materials.add(Color::srgb_u8(244, 90, 90)),
+            transform: Transform::from_xyz(0.0, ground + PLAYER_HALF_HEIGHT, 0.0),
+            ..default()
+        },
+        RigidBody::KinematicPositionBased,
+        Collider::cuboid(PLAYER_HALF_HEIGHT, PLAYER_HALF_HEIGHT, PLAYER_HALF_HEIGHT),
+        KinematicCharacterController {
+            autostep: Some(CharacterAutostep {
+                max_height: CharacterLength::Absolute(player_config.step_height),
+                min_width: CharacterLength::Absolute(0.2),
+                include_dynamic_bodies: false,
+            }),
+            max_slope_climb_angle: player_config.max_slope_angle.to_radians(),
+            min_slope_slide_angle: player_config.max_slope_angle.to_radians(),
+            snap_to_ground: Some(CharacterLength::Absolute(player_config.snap_distance)),
+            ..default()
+        },
+        Player::default(),
+        picking::Selectable,
+        camera_system::ThirdPersonCameraTarget,
+    ));
+}
+
+fn player_movement(
+    time: Res<Time>,
+    actions: Res<ActionState>,
+    terrain: terrain_generator::TerrainQuery,
+    player_config: Res<config_parser::PlayerConfig>,
+    mut player_q: Query<(
+        Entity,
+        &mut Player,
+        &mut Transform,
+        &mut KinematicCharacterController,
+        Option<&KinematicCharacterControllerOutput>,
+    )>,
+    cam_q: Query<
+        (&Transform, &camera_system::CameraTarget),
+        (
+            With<Camera3d>,
+            Without<Player>,
+            Without<camera_system::StrategyCamera>,
+        ),
+    >,
+) {
+    for (entity, mut player, mut player_transform, mut controller, output) in player_q.iter_mut() {
+        // Moves relative to the camera following this player. The movement actions pan the
+        // strategy camera instead while it is active. Stick input is analog, so a half tilted
+        // stick walks at half speed.
+        let mut direction = Vec3::ZERO;
+        let cam = cam_q
+            .iter()
+            .find(|(_, target)| target.0 == entity)
+            .map(|(cam, _)| cam);
+        if let Some(cam) = cam {
+            let forward = Vec3::new(cam.forward().x, 0.0, cam.forward().z).normalize_or_zero();
+            let right = Vec3::new(cam.right().x, 0.0, cam.right().z).normalize_or_zero();
+            direction += forward * actions.axis(InputAction::MoveBack, InputAction::MoveForward);
+            direction += right * actions.axis(InputAction::MoveLeft, InputAction::MoveRight);
+        }
+        let mut movement =
+            direction.clamp_length_max(1.0) * player_config.move_speed * time.delta_seconds();
+        movement *= terrain_speed_factor(
+            &terrain,
+            &player_config,
+            player_transform.translation,
+            movement,
+        );
+
+        let grounded = output.map_or(false, |output| output.grounded);
+        if grounded {
+            player.vertical_velocity = 0.0;
+        } else {
+            player.vertical_velocity -= player_config.gravity * time.delta_seconds();
+        }
+        movement.y = player.vertical_velocity * time.delta_seconds();
+        controller.translation = Some(movement);
+
+        if direction.length_squared() > 0.0 {
+            player_transform.look_to(direction, Vec3::Y);
+        }
+    }
+}
+
+// The character controller handles collisions, this adds the gameplay rules on top. Climbing
+// is slowed in proportion to the slope and refused above the configured maximum, and the player
+// cannot wade any deeper once the water is past the configured depth. Moving downhill or into
+// shallower water is always allowed so the player can never get stuck, and the map edge is
+// never crossed.
+fn terrain_speed_factor(
+    terrain: &terrain_generator::TerrainQuery,
+    player_config: &config_parser::PlayerConfig,
+    position: Vec3,
+    movement: Vec3,
+) -> f32 {
+    if movement.length_squared() == 0.0 {
+        return 1.0;
+    }
+    let Some(current_height) = terrain.height_at(position.x, position.z) else {
+        return 1.0;
+    };
+    let current_depth = terrain
+        .water_depth_at(position.x, position.z)
+        .unwrap_or(0.0);
+
+    let target = position + movement;
+    let (Some(target_height), Some(slope), Some(depth)) = (
+        terrain.height_at(target.x, target.z),
+        terrain.slope_at(target.x, target.z),
+        terrain.water_depth_at(target.x, target.z),
+    ) else {
+        return 0.0;
+    };
+    if depth > player_config.max_water_depth && depth > current_depth {
+        return 0.0;
+    }
+    if target_height > current_height {
+        let max_slope = player_config.max_slope_angle.to_radians();
+        if slope > max_slope {
+            return 0.0;
+        }
+        return 1.0 - player_config.slope_slowdown * slope / max_slope;
+    }
+    return 1.0;
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::SystemState;
+    use noise::utils::NoiseMap;
+
+    use super::*;
+    use crate::config_parser::{load_engine_config, load_map_config, load_player_config};
+    use crate::terrain_generator::{TerrainMap, TerrainQuery};
+
+    const WORLD_SIZE: usize = 8;
+    const WORLD_HEIGHT: f32 = 5.0;
+
+    // World with a heightmap from `height`, in the same 0..1 units as the noise
+    fn terrain_world(sea_level: f64, height: impl Fn(usize, usize) -> f64) -> World {
+        let mut map = NoiseMap::new(WORLD_SIZE, WORLD_SIZE);
+        for x in 0..WORLD_SIZE {
+            for z in 0..WORLD_SIZE {
+                map.set_value(x, z, height(x, z));
+            }
+        }
+        let mut world = World::new();
+        world.insert_resource(TerrainMap { map });
+        world.insert_resource(config_parser::EngineConfig {
+            world_size: WORLD_SIZE,
+            world_height: WORLD_HEIGHT,
+            ..load_engine_config()
+        });
+        world.insert_resource(config_parser::MapConfig {
+            sea_level,
+            ..load_map_config()
+        });
+        return world;
+    }
+
+    fn player_config() -> config_parser::PlayerConfig {
+        return config_parser::PlayerConfig {
+            max_slope_angle: 40.0,
+            slope_slowdown: 0.6,
+            max_water_depth: 0.5,
+            ..load_player_config()
+        };
+    }
+
+    fn speed_factor(world: &mut World, position: Vec3, movement: Vec3) -> f32 {
+        let mut state: SystemState<TerrainQuery> = SystemState::new(world);
+        let terrain = state.get(world);
+        return terrain_speed_factor(&terrain, &player_config(), position, movement);
+    }
+
+    // Rises along z at the given angle
+    fn ramp(degrees: f32) -> impl Fn(usize, usize) -> f64 {
+        let rise = degrees.to_radians().tan() / WORLD_HEIGHT;
+        return move |_, z| 0.2 + z as f64 * rise as f64;
+    }
+
+    #[test]
+    fn flat_ground_keeps_full_speed() {
+        let mut world = terrain_world(0.0, |_, _| 0.5);
+        let position = Vec3::new(3.5, 2.5, 3.5);
+        assert_eq!(
+            speed_factor(&mut world, position, Vec3::new(0.5, 0.0, 0.2)),
+            1.0
+        );
+        assert_eq!(speed_factor(&mut world, position, Vec3::ZERO), 1.0);
+    }
+
+    #[test]
+    fn inclines_slow_climbing_in_proportion_to_the_slope() {
+        let mut world = terrain_world(0.0, ramp(20.0));
+        let position = Vec3::new(3.5, 0.0, 3.5);
+        let uphill = speed_factor(&mut world, position, Vec3::new(0.0, 0.0, 0.3));
+        assert!(
+            (uphill - (1.0 - 0.6 * 20.0 / 40.0)).abs() < 1e-3,
+            "{}",
+            uphill
+        );
+        let downhill = speed_factor(&mut world, position, Vec3::new(0.0, 0.0, -0.3));
+        assert_eq!(downhill, 1.0);
+        // Walking across the slope neither climbs nor descends
+        let across = speed_factor(&mut world, position, Vec3::new(0.3, 0.0, 0.0));
+        assert_eq!(across, 1.0);
+    }
+
+    #[test]
+    fn slopes_steeper_than_the_maximum_are_refused() {
+        let mut world = terrain_world(0.0, ramp(60.0));
+        let position = Vec3::new(3.5, 0.0, 3.5);
+        assert_eq!(
+            speed_factor(&mut world, position, Vec3::new(0.0, 0.0, 0.3)),
+            0.0
+        );
+        assert_eq!(
+            speed_factor(&mut world, position, Vec3::new(0.0, 0.0, -0.3)),
+            1.0
+        );
+    }
+
+    #[test]
+    fn deep_water_blocks_wading_in_but_not_out() {
+        // Sea level is 2.5. The shore at x 0..=1 is dry, x 2..=3 is 0.25 deep and from x 4 on
+        // it is 1.0 deep, past the 0.5 limit.
+        let mut world = terrain_world(0.5, |x, _| match x {
+            0..=1 => 0.6,
+            2..=3 => 0.45,
+            _ => 0.3,
+        });
+        let step = Vec3::new(0.5, 0.0, 0.0);
+        let shore = Vec3::new(1.0, 3.0, 3.5);
+        let shallows = Vec3::new(2.5, 2.25, 3.5);
+        let deep = Vec3::new(5.5, 1.5, 3.5);
+        assert_eq!(
+            speed_factor(&mut world, shore, Vec3::new(1.5, 0.0, 0.0)),
+            1.0
+        );
+        assert_eq!(
+            speed_factor(&mut world, shallows, Vec3::new(2.0, 0.0, 0.0)),
+            0.0
+        );
+        // Already out of depth, so moving through equally deep water or back to shore is fine
+        assert_eq!(speed_factor(&mut world, deep, step), 1.0);
+        assert_eq!(speed_factor(&mut world, deep, -step * 7.0), 1.0);
+    }
+
+    #[test]
+    fn the_map_edge_is_never_crossed() {
+        let mut world = terrain_world(0.0, |_, _| 0.5);
+        let edge = Vec3::new(0.2, 2.5, 3.5);
+        assert_eq!(
+            speed_factor(&mut world, edge, Vec3::new(-0.5, 0.0, 0.0)),
+            0.0
+        );
+    }
+}
@@ -0,0 +1,172 @@
+use bevy::prelude::*;
+
+use crate::config_parser::EngineConfig;
+
+// There are three coordinate spaces for the terrain:
+// - grid space is the (x, z) index of a vertex in the heightmap, 0..world_size on both axes
+// - world space is the bevy translation, one world unit per grid cell
+// - chunk space is the (x, z) index of a chunk mesh, 0..chunks_per_axis on both axes
+//
+// Neighbouring chunks share their border row of vertices so the meshes stitch without seams,
+// which means a chunk advances `chunk_size - 1` grid cells. Chunks on the far edges are
+// clipped to the heightmap instead of reading past it.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ChunkCoord {
+    pub x: usize,
+    pub z: usize,
+}
+
+impl ChunkCoord {
+    pub fn new(x: usize, z: usize) -> Self {
+        Self { x, z }
+    }
+
+    pub fn stride(engine_config: &EngineConfig) -> usize {
+        return engine_config.chunk_size - 1;
+    }
+
+    pub fn chunks_per_axis(engine_config: &EngineConfig) -> usize {
+        let cells = engine_config.world_size - 1;
+        return cells.div_ceil(Self::stride(engine_config));
+    }
+
+    pub fn iter(engine_config: &EngineConfig) -> impl Iterator<Item = ChunkCoord> {
+        let chunks = Self::chunks_per_axis(engine_config);
+        (0..chunks).flat_map(move |x| (0..chunks).map(move |z| ChunkCoord::new(x, z)))
+    }
+
+    pub fn index(&self, engine_config: &EngineConfig) -> usize {
+        return self.x * Self::chunks_per_axis(engine_config) + self.z;
+    }
+
+    pub fn grid_origin(&self, engine_config: &EngineConfig) -> (usize, usize) {
+        let stride = Self::stride(engine_config);
+        return (self.x * stride, self.z * stride);
+    }
+
+    pub fn grid_size(&self, engine_config: &EngineConfig) -> (usize, usize) {
+        let (x_start, z_start) = self.grid_origin(engine_config);
+        return (
+            engine_config
+                .chunk_size
+                .min(engine_config.world_size - x_start),
+            engine_config
+                .chunk_size
+                .min(engine_config.world_size - z_start),
+        );
+    }
+
+    pub fn world_origin(&self, engine_config: &EngineConfig) -> Vec3 {
+        let (x, z) = self.grid_origin(engine_config);
+        return grid_to_world(x, z);
+    }
+
+    pub fn from_grid(x: usize, z: usize, engine_config: &EngineConfig) -> Option<ChunkCoord> {
+        if x >= engine_config.world_size || z >= engine_config.world_size {
+            return None;
+        }
+        let stride = Self::stride(engine_config);
+        let last = Self::chunks_per_axis(engine_config) - 1;
+        return Some(ChunkCoord::new(
+            (x / stride).min(last),
+            (z / stride).min(last),
+        ));
+    }
+
+    pub fn from_world(position: Vec3, engine_config: &EngineConfig) -> Option<ChunkCoord> {
+        let (x, z) = world_to_grid(position, engine_config)?;
+        return Self::from_grid(x, z, engine_config);
+    }
+}
+
+pub fn grid_to_world(x: usize, z: usize) -> Vec3 {
+    return Vec3::new(x as f32, 0.0, z as f32);
+}
+
+pub fn world_to_grid(position: Vec3, engine_config: &EngineConfig) -> Option<(usize, usize)> {
+    let max = (engine_config.world_size - 1) as f32;
+    if !(0.0..=max).contains(&position.x) || !(0.0..=max).contains(&position.z) {
+        return None;
+    }
+    return Some((position.x.floor() as usize, position.z.floor() as usize));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_parser::load_engine_config;
+
+    // World and chunk sizes in vertices, some with a clipped chunk on the far edge
+    const SIZES: [(usize, usize); 8] = [
+        (2, 2),
+        (7, 7),
+        (9, 8),
+        (10, 4),
+        (16, 4),
+        (17, 5),
+        (20, 6),
+        (33, 8),
+    ];
+
+    fn engine_config(world_size: usize, chunk_size: usize) -> EngineConfig {
+        return EngineConfig {
+            world_size,
+            chunk_size,
+            ..load_engine_config()
+        };
+    }
+
+    #[test]
+    fn chunks_tile_the_grid_sharing_one_border_row() {
+        for (world_size, chunk_size) in SIZES {
+            let config = engine_config(world_size, chunk_size);
+            let chunks = ChunkCoord::chunks_per_axis(&config);
+            let mut expected_start = 0;
+            for x in 0..chunks {
+                let chunk = ChunkCoord::new(x, 0);
+                let (start, _) = chunk.grid_origin(&config);
+                let (width, _) = chunk.grid_size(&config);
+                assert_eq!(
+                    start,
+                    expected_start,
+                    "{:?} in {:?}",
+                    chunk,
+                    (world_size, chunk_size)
+                );
+                assert!(
+                    width >= 2,
+                    "{:?} in {:?} has no cells",
+                    chunk,
+                    (world_size, chunk_size)
+                );
+                assert!(width <= chunk_size);
+                assert!(start + width <= world_size);
+                expected_start = start + width - 1;
+            }
+            assert_eq!(
+                expected_start,
+                world_size - 1,
+                "{:?}",
+                (world_size, chunk_size)
+            );
+        }
+    }
+
+    #[test]
+    fn every_grid_point_belongs_to_the_chunk_it_maps_to() {
+        for (world_size, chunk_size) in SIZES {
+            let config = engine_config(world_size, chunk_size);
+            for x in 0..world_size {
+                for z in 0..world_size {
+                    let chunk = ChunkCoord::from_grid(x, z, &config).unwrap();
+                    let (x_start, z_start) = chunk.grid_origin(&config);
+                    let (width, depth) = chunk.grid_size(&config);
+                    assert!((x_start..x_start + width).contains(&x));
+                    assert!((z_start..z_start + depth).contains(&z));
+                    assert!(chunk.index(&config) < ChunkCoord::iter(&config).count());
+                }
+            }
+            assert_eq!(ChunkCoord::from_grid(world_size, 0, &config), None);
+        }
+    }
+}
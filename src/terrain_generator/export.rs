@@ -0,0 +1,510 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, VertexAttributeValues};
+
+use crate::config_parser;
+use crate::loading_screen::AppState;
+use crate::terrain_generator::chunk::ChunkCoord;
+use crate::terrain_generator::{create_texture_map, mesh_generator, TerrainMaterial};
+
+pub struct TerrainExportPlugin;
+
+impl Plugin for TerrainExportPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ExportTerrainEvent>().add_systems(
+            Update,
+            (request_terrain_export, export_terrain_on_request)
+                .chain()
+                .run_if(in_state(AppState::InGame)),
+        );
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    Glb,
+    Obj,
+}
+
+impl ExportFormat {
+    pub fn parse(name: &str) -> Option<ExportFormat> {
+        match name.to_ascii_lowercase().as_str() {
+            "glb" | "gltf" => Some(ExportFormat::Glb),
+            "obj" => Some(ExportFormat::Obj),
+            _ => None,
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Glb => "glb",
+            ExportFormat::Obj => "obj",
+        }
+    }
+}
+
+#[derive(Event, Clone, Copy, Debug)]
+pub struct ExportTerrainEvent {
+    pub format: ExportFormat,
+    pub per_chunk: bool,
+}
+
+struct MeshData {
+    name: String,
+    translation: Vec3,
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    colors: Vec<[f32; 4]>,
+    indices: Vec<u32>,
+}
+
+impl MeshData {
+    // Terrain meshes are colored by `TerrainMaterial` rather than vertex colors, so the colors
+    // are baked from the material unless the mesh carries its own.
+    fn from_mesh(
+        chunk: &ChunkCoord,
+        translation: Vec3,
+        mesh: &Mesh,
+        material: &TerrainMaterial,
+    ) -> Option<MeshData> {
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            return None;
+        };
+        let Some(VertexAttributeValues::Float32x3(normals)) =
+            mesh.attribute(Mesh::ATTRIBUTE_NORMAL)
+        else {
+            return None;
+        };
+        let colors = match mesh.attribute(Mesh::ATTRIBUTE_COLOR) {
+            Some(VertexAttributeValues::Float32x4(colors)) => colors.clone(),
+            _ => positions
+                .iter()
+                .zip(normals.iter())
+                .map(|(position, normal)| {
+                    material
+                        .color_at(position[1] + translation.y, Vec3::from(*normal))
+                        .to_f32_array()
+                })
+                .collect(),
+        };
+        let indices = match mesh.indices()? {
+            Indices::U16(indices) => indices.iter().map(|index| *index as u32).collect(),
+            Indices::U32(indices) => indices.clone(),
+        };
+        return Some(MeshData {
+            name: format!("chunk_{}_{}", chunk.x, chunk.z),
+            translation,
+            positions: positions.clone(),
+            normals: normals.clone(),
+            colors,
+            indices,
+        });
+    }
+}
+
+fn request_terrain_export(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut export_evw: EventWriter<ExportTerrainEvent>,
+) {
+    let per_chunk = keys.pressed(KeyCode::ShiftLeft) || keys.pressed(KeyCode::ShiftRight);
+    if keys.just_pressed(KeyCode::F9) {
+        export_evw.send(ExportTerrainEvent {
+            format: ExportFormat::Glb,
+            per_chunk,
+        });
+    }
+    if keys.just_pressed(KeyCode::F10) {
+        export_evw.send(ExportTerrainEvent {
+            format: ExportFormat::Obj,
+            per_chunk,
+        });
+    }
+}
+
+fn export_terrain_on_request(
+    mut export_evr: EventReader<ExportTerrainEvent>,
+    chunk_q: Query<(&ChunkCoord, &Transform, &Handle<Mesh>)>,
+    meshes: Res<Assets<Mesh>>,
+    map_config: Res<config_parser::MapConfig>,
+    engine_config: Res<config_parser::EngineConfig>,
+) {
+    let material = TerrainMaterial::new(&map_config, &engine_config);
+    for ev in export_evr.read() {
+        let Some(directory) = config_parser::user_export_dir() else {
+            error!("Could not find a directory to export the terrain to.");
+            continue;
+        };
+        let mut chunks: Vec<MeshData> = chunk_q
+            .iter()
+            .filter_map(|(chunk, transform, handle)| {
+                MeshData::from_mesh(chunk, transform.translation, meshes.get(handle)?, &material)
+            })
+            .collect();
+        chunks.sort_by(|a, b| a.name.cmp(&b.name));
+        match write_terrain(&chunks, ev.format, ev.per_chunk, &directory) {
+            Ok(files) => info!(
+                "Exported terrain to {} file(s) in {:?}",
+                files.len(),
+                directory
+            ),
+            Err(e) => error!("Failed to export terrain: {}", e),
+        }
+    }
+}
+
+// Generates the world from the config files and exports it without opening a window. Lists the
+// exported files on stdout and returns the error for the command line to report.
+pub fn export_headless(format: ExportFormat, per_chunk: bool) -> Result<(), String> {
+    let map_config = config_parser::load_map_config();
+    let engine_config = config_parser::load_engine_config();
+    let map = futures_lite::future::block_on(create_texture_map(
+        map_config.clone(),
+        engine_config.clone(),
+    ));
+    let meshes = mesh_generator::generate_low_poly_terrain(
+        engine_config.clone(),
+        map_config.sea_level,
+        map.iter().copied().collect(),
+    )
+    .terrain;
+    let material = TerrainMaterial::new(&map_config, &engine_config);
+    let chunks: Vec<MeshData> = ChunkCoord::iter(&engine_config)
+        .filter_map(|chunk| {
+            MeshData::from_mesh(
+                &chunk,
+                chunk.world_origin(&engine_config),
+                &meshes[chunk.index(&engine_config)],
+                &material,
+            )
+        })
+        .collect();
+
+    let Some(directory) = config_parser::user_export_dir() else {
+        return Err("Could not find a directory to export the terrain to.".to_string());
+    };
+    let files = write_terrain(&chunks, format, per_chunk, &directory).map_err(|e| e.to_string())?;
+    for file in files {
+        println!("Exported {}", file.to_string_lossy());
+    }
+    return Ok(());
+}
+
+fn write_terrain(
+    chunks: &[MeshData],
+    format: ExportFormat,
+    per_chunk: bool,
+    directory: &Path,
+) -> io::Result<Vec<PathBuf>> {
+    fs::create_dir_all(directory)?;
+    let mut files = Vec::new();
+    if per_chunk {
+        for chunk in chunks {
+            let target = directory.join(format!("{}.{}", chunk.name, format.extension()));
+            write_file(std::slice::from_ref(chunk), format, &target)?;
+            files.push(target);
+        }
+    } else {
+        let target = directory.join(format!("terrain.{}", format.extension()));
+        write_file(chunks, format, &target)?;
+        files.push(target);
+    }
+    return Ok(files);
+}
+
+fn write_file(chunks: &[MeshData], format: ExportFormat, target: &Path) -> io::Result<()> {
+    let bytes = match format {
+        ExportFormat::Glb => encode_glb(chunks)?,
+        ExportFormat::Obj => encode_obj(chunks).into_bytes(),
+    };
+    let mut file = fs::File::create(target)?;
+    file.write_all(&bytes)?;
+    return Ok(());
+}
+
+// Wavefront OBJ has no node transforms, so each chunk's translation is baked into its vertices.
+// Vertex colors use the widely supported `v x y z r g b` extension.
+fn encode_obj(chunks: &[MeshData]) -> String {
+    let mut obj = String::from("# Foundations of a Kingdom terrain\n");
+    let mut vertex_offset = 1;
+    for chunk in chunks {
+        let _ = writeln!(obj, "o {}", chunk.name);
+        for (position, color) in chunk.positions.iter().zip(chunk.colors.iter()) {
+            let _ = writeln!(
+                obj,
+                "v {} {} {} {} {} {}",
+                position[0] + chunk.translation.x,
+                position[1] + chunk.translation.y,
+                position[2] + chunk.translation.z,
+                color[0],
+                color[1],
+                color[2]
+            );
+        }
+        for normal in chunk.normals.iter() {
+            let _ = writeln!(obj, "vn {} {} {}", normal[0], normal[1], normal[2]);
+        }
+        for triangle in chunk.indices.chunks(3) {
+            let a = triangle[0] as usize + vertex_offset;
+            let b = triangle[1] as usize + vertex_offset;
+            let c = triangle[2] as usize + vertex_offset;
+            let _ = writeln!(obj, "f {a}//{a} {b}//{b} {c}//{c}");
+        }
+        vertex_offset += chunk.positions.len();
+    }
+    return obj;
+}
+
+// Binary glTF 2.0 with one node per chunk. All attributes live in a single buffer, and every
+// attribute is made of 4 byte components so the views never need padding. glTF does not allow
+// an empty buffer, so there must be at least one vertex to export.
+fn encode_glb(chunks: &[MeshData]) -> io::Result<Vec<u8>> {
+    const ARRAY_BUFFER: u32 = 34962;
+    const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+    const FLOAT: u32 = 5126;
+    const UNSIGNED_INT: u32 = 5125;
+
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut buffer_views = Vec::new();
+    let mut accessors = Vec::new();
+    let mut meshes = Vec::new();
+    let mut nodes = Vec::new();
+
+    let mut push_view = |buffer: &mut Vec<u8>, data: Vec<u8>, target: u32| -> usize {
+        buffer_views.push(format!(
+            "{{\"buffer\":0,\"byteOffset\":{},\"byteLength\":{},\"target\":{}}}",
+            buffer.len(),
+            data.len(),
+            target
+        ));
+        buffer.extend_from_slice(&data);
+        return buffer_views.len() - 1;
+    };
+
+    for (index, chunk) in chunks.iter().enumerate() {
+        let (min, max) = chunk.positions.iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), position| {
+                let position = Vec3::from(*position);
+                (min.min(position), max.max(position))
+            },
+        );
+
+        let view = push_view(&mut buffer, floats_to_bytes(&chunk.positions), ARRAY_BUFFER);
+        accessors.push(format!(
+            "{{\"bufferView\":{},\"componentType\":{},\"count\":{},\"type\":\"VEC3\",\"min\":[{},{},{}],\"max\":[{},{},{}]}}",
+            view, FLOAT, chunk.positions.len(), min.x, min.y, min.z, max.x, max.y, max.z
+        ));
+        let view = push_view(&mut buffer, floats_to_bytes(&chunk.normals), ARRAY_BUFFER);
+        accessors.push(format!(
+            "{{\"bufferView\":{},\"componentType\":{},\"count\":{},\"type\":\"VEC3\"}}",
+            view,
+            FLOAT,
+            chunk.normals.len()
+        ));
+        let view = push_view(&mut buffer, floats_to_bytes(&chunk.colors), ARRAY_BUFFER);
+        accessors.push(format!(
+            "{{\"bufferView\":{},\"componentType\":{},\"count\":{},\"type\":\"VEC4\"}}",
+            view,
+            FLOAT,
+            chunk.colors.len()
+        ));
+        let index_bytes = chunk.indices.iter().flat_map(|i| i.to_le_bytes()).collect();
+        let view = push_view(&mut buffer, index_bytes, ELEMENT_ARRAY_BUFFER);
+        accessors.push(format!(
+            "{{\"bufferView\":{},\"componentType\":{},\"count\":{},\"type\":\"SCALAR\"}}",
+            view,
+            UNSIGNED_INT,
+            chunk.indices.len()
+        ));
+
+        let first = accessors.len() - 4;
+        meshes.push(format!(
+            "{{\"name\":\"{}\",\"primitives\":[{{\"attributes\":{{\"POSITION\":{},\"NORMAL\":{},\"COLOR_0\":{}}},\"indices\":{},\"mode\":4}}]}}",
+            chunk.name,
+            first,
+            first + 1,
+            first + 2,
+            first + 3
+        ));
+        nodes.push(format!(
+            "{{\"name\":\"{}\",\"mesh\":{},\"translation\":[{},{},{}]}}",
+            chunk.name, index, chunk.translation.x, chunk.translation.y, chunk.translation.z
+        ));
+    }
+
+    if buffer.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "There is no terrain to export.",
+        ));
+    }
+
+    let scene_nodes: Vec<String> = (0..nodes.len()).map(|i| i.to_string()).collect();
+    let mut json = format!(
+        "{{\"asset\":{{\"version\":\"2.0\",\"generator\":\"Foundations of a Kingdom\"}},\"scene\":0,\"scenes\":[{{\"nodes\":[{}]}}],\"nodes\":[{}],\"meshes\":[{}],\"accessors\":[{}],\"bufferViews\":[{}],\"buffers\":[{{\"byteLength\":{}}}]}}",
+        scene_nodes.join(","),
+        nodes.join(","),
+        meshes.join(","),
+        accessors.join(","),
+        buffer_views.join(","),
+        buffer.len()
+    )
+    .into_bytes();
+    while json.len() % 4 != 0 {
+        json.push(b' ');
+    }
+
+    let total_length = 12 + 8 + json.len() + 8 + buffer.len();
+    let mut glb = Vec::with_capacity(total_length);
+    glb.extend_from_slice(b"glTF");
+    glb.extend_from_slice(&2u32.to_le_bytes());
+    glb.extend_from_slice(&(total_length as u32).to_le_bytes());
+    glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+    glb.extend_from_slice(b"JSON");
+    glb.extend_from_slice(&json);
+    glb.extend_from_slice(&(buffer.len() as u32).to_le_bytes());
+    glb.extend_from_slice(b"BIN\0");
+    glb.extend_from_slice(&buffer);
+    return Ok(glb);
+}
+
+fn floats_to_bytes<const N: usize>(values: &[[f32; N]]) -> Vec<u8> {
+    return values
+        .iter()
+        .flat_map(|value| value.iter().flat_map(|component| component.to_le_bytes()))
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A single triangle, or a quad made of two when `quad` is set
+    fn chunk(name: &str, translation: Vec3, quad: bool) -> MeshData {
+        let mut positions = vec![[0.0, 1.0, 0.0], [1.0, -2.0, 0.0], [0.0, 0.5, 3.0]];
+        let mut indices = vec![0, 1, 2];
+        if quad {
+            positions.push([1.0, 4.0, 1.0]);
+            indices.extend([1, 3, 2]);
+        }
+        return MeshData {
+            name: name.to_string(),
+            translation,
+            normals: vec![[0.0, 1.0, 0.0]; positions.len()],
+            colors: vec![[0.2, 0.4, 0.6, 1.0]; positions.len()],
+            positions,
+            indices,
+        };
+    }
+
+    fn read_u32(bytes: &[u8], offset: usize) -> usize {
+        return u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize;
+    }
+
+    #[test]
+    fn glb_chunks_are_padded_and_add_up() {
+        // Names of different lengths so the JSON needs padding for some of them
+        for name in ["a", "ab", "abc", "abcd"] {
+            let glb = encode_glb(&[chunk(name, Vec3::ZERO, false)]).unwrap();
+            assert_eq!(&glb[0..4], b"glTF");
+            assert_eq!(read_u32(&glb, 4), 2);
+            assert_eq!(read_u32(&glb, 8), glb.len());
+
+            let json_length = read_u32(&glb, 12);
+            assert_eq!(&glb[16..20], b"JSON");
+            assert_eq!(json_length % 4, 0);
+            let bin_start = 20 + json_length;
+            let bin_length = read_u32(&glb, bin_start);
+            assert_eq!(&glb[bin_start + 4..bin_start + 8], b"BIN\0");
+            assert_eq!(bin_length % 4, 0);
+            assert_eq!(bin_start + 8 + bin_length, glb.len());
+
+            let json: serde_yaml::Value =
+                serde_yaml::from_str(std::str::from_utf8(&glb[20..bin_start]).unwrap()).unwrap();
+            assert_eq!(
+                json["buffers"][0]["byteLength"].as_u64(),
+                Some(bin_length as u64)
+            );
+        }
+    }
+
+    #[test]
+    fn glb_accessors_describe_each_chunk() {
+        let chunks = [
+            chunk("chunk_0_0", Vec3::ZERO, false),
+            chunk("chunk_1_0", Vec3::new(8.0, 0.0, 0.0), true),
+        ];
+        let glb = encode_glb(&chunks).unwrap();
+        let json_length = read_u32(&glb, 12);
+        let json: serde_yaml::Value =
+            serde_yaml::from_str(std::str::from_utf8(&glb[20..20 + json_length]).unwrap()).unwrap();
+
+        let accessors = json["accessors"].as_sequence().unwrap();
+        assert_eq!(accessors.len(), 8);
+        let counts: Vec<u64> = accessors
+            .iter()
+            .map(|accessor| accessor["count"].as_u64().unwrap())
+            .collect();
+        assert_eq!(counts, vec![3, 3, 3, 3, 4, 4, 4, 6]);
+        let bounds = |accessor: &serde_yaml::Value, key: &str| -> Vec<f64> {
+            return accessor[key]
+                .as_sequence()
+                .unwrap()
+                .iter()
+                .map(|value| value.as_f64().unwrap())
+                .collect();
+        };
+        assert_eq!(bounds(&accessors[0], "min"), vec![0.0, -2.0, 0.0]);
+        assert_eq!(bounds(&accessors[0], "max"), vec![1.0, 1.0, 3.0]);
+        assert_eq!(bounds(&accessors[4], "max"), vec![1.0, 4.0, 3.0]);
+        // Translations stay on the nodes rather than in the vertices
+        assert_eq!(json["nodes"][1]["translation"][0].as_f64(), Some(8.0));
+
+        // Each buffer view fits in the buffer
+        let buffer_length = json["buffers"][0]["byteLength"].as_u64().unwrap();
+        for view in json["bufferViews"].as_sequence().unwrap() {
+            let end = view["byteOffset"].as_u64().unwrap() + view["byteLength"].as_u64().unwrap();
+            assert!(end <= buffer_length);
+        }
+    }
+
+    #[test]
+    fn glb_without_terrain_is_an_error() {
+        assert!(encode_glb(&[]).is_err());
+    }
+
+    #[test]
+    fn obj_indices_continue_across_chunks() {
+        let chunks = [
+            chunk("chunk_0_0", Vec3::ZERO, true),
+            chunk("chunk_1_0", Vec3::new(8.0, 0.0, 0.0), false),
+        ];
+        let obj = encode_obj(&chunks);
+        let lines: Vec<&str> = obj.lines().collect();
+        let faces: Vec<&str> = lines
+            .iter()
+            .copied()
+            .filter(|line| line.starts_with("f "))
+            .collect();
+        assert_eq!(
+            faces,
+            vec!["f 1//1 2//2 3//3", "f 2//2 4//4 3//3", "f 5//5 6//6 7//7"]
+        );
+        assert_eq!(
+            lines.iter().filter(|line| line.starts_with("v ")).count(),
+            7
+        );
+        assert_eq!(
+            lines.iter().filter(|line| line.starts_with("vn ")).count(),
+            7
+        );
+        // Translations are baked into the vertices
+        assert!(lines.contains(&"v 8 1 0 0.2 0.4 0.6"));
+    }
+}
@@ -0,0 +1,218 @@
+use std::fs;
+use std::path::Path;
+
+use bevy::render::{
+    render_asset::RenderAssetUsages,
+    render_resource::TextureFormat,
+    texture::{CompressedImageFormats, Image, ImageSampler, ImageType},
+};
+use noise::utils::NoiseMap;
+
+use crate::config_parser::EngineConfig;
+
+// Loads a hand authored or real world heightmap in place of the noise stage. PNG files may be
+// 8 or 16 bit grayscale, `.raw` and `.r16` files are square 16 bit little endian samples, and
+// `.r32` files are square 32 bit little endian floats. The samples are resampled to the world
+// size and stretched over `range`, which is -1..1 by default like `generate_texture` produces.
+pub fn load_heightmap(
+    path: &Path,
+    engine_config: &EngineConfig,
+    range: [f64; 2],
+) -> Result<NoiseMap, String> {
+    let bytes = fs::read(path).map_err(|e| e.to_string())?;
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let (samples, width, height) = match extension.as_str() {
+        "png" => decode_png(&bytes)?,
+        "raw" | "r16" => {
+            let samples: Vec<f64> = bytes
+                .chunks_exact(2)
+                .map(|sample| u16::from_le_bytes([sample[0], sample[1]]) as f64)
+                .collect();
+            let side = square_side(samples.len())?;
+            (samples, side, side)
+        }
+        "r32" => {
+            let samples: Vec<f64> = bytes
+                .chunks_exact(4)
+                .map(|sample| {
+                    f32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]) as f64
+                })
+                .collect();
+            let side = square_side(samples.len())?;
+            (samples, side, side)
+        }
+        _ => return Err(format!("Unsupported heightmap format {:?}", extension)),
+    };
+    if width < 2 || height < 2 {
+        return Err(format!(
+            "Heightmap must be at least 2x2, got {}x{}",
+            width, height
+        ));
+    }
+
+    return Ok(resample(
+        &samples,
+        width,
+        height,
+        engine_config.world_size,
+        range,
+    ));
+}
+
+// Resamples the heightmap to `size` points a side and stretches its lowest and highest samples
+// over `range`. A flat heightmap ends up at the low end.
+fn resample(
+    samples: &[f64],
+    width: usize,
+    height: usize,
+    size: usize,
+    range: [f64; 2],
+) -> NoiseMap {
+    let (min, max) = samples
+        .iter()
+        .fold((f64::MAX, f64::MIN), |(min, max), sample| {
+            (min.min(*sample), max.max(*sample))
+        });
+    let spread = if max > min { max - min } else { 1.0 };
+    let [low, high] = range;
+
+    let mut map = NoiseMap::new(size, size);
+    for z in 0..size {
+        for x in 0..size {
+            let sample = sample_bilinear(
+                samples,
+                width,
+                height,
+                x as f64 / (size - 1) as f64 * (width - 1) as f64,
+                z as f64 / (size - 1) as f64 * (height - 1) as f64,
+            );
+            map.set_value(x, z, low + (sample - min) / spread * (high - low));
+        }
+    }
+    return map;
+}
+
+fn decode_png(bytes: &[u8]) -> Result<(Vec<f64>, usize, usize), String> {
+    let image = Image::from_buffer(
+        bytes,
+        ImageType::Extension("png"),
+        CompressedImageFormats::NONE,
+        false,
+        ImageSampler::Default,
+        RenderAssetUsages::MAIN_WORLD,
+    )
+    .map_err(|e| e.to_string())?;
+    let width = image.width() as usize;
+    let height = image.height() as usize;
+    // Grayscale images only need their first channel
+    let samples = match image.texture_descriptor.format {
+        TextureFormat::R16Uint => image
+            .data
+            .chunks_exact(2)
+            .map(|sample| u16::from_le_bytes([sample[0], sample[1]]) as f64)
+            .collect(),
+        TextureFormat::Rgba16Unorm => image
+            .data
+            .chunks_exact(8)
+            .map(|pixel| u16::from_le_bytes([pixel[0], pixel[1]]) as f64)
+            .collect(),
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => image
+            .data
+            .chunks_exact(4)
+            .map(|pixel| pixel[0] as f64)
+            .collect(),
+        format => return Err(format!("Unsupported PNG pixel format {:?}", format)),
+    };
+    return Ok((samples, width, height));
+}
+
+fn square_side(samples: usize) -> Result<usize, String> {
+    let side = (samples as f64).sqrt().round() as usize;
+    if side * side != samples {
+        return Err(format!(
+            "Raw heightmap with {} samples is not square",
+            samples
+        ));
+    }
+    return Ok(side);
+}
+
+fn sample_bilinear(samples: &[f64], width: usize, height: usize, x: f64, z: f64) -> f64 {
+    let x0 = (x.floor() as usize).min(width - 2);
+    let z0 = (z.floor() as usize).min(height - 2);
+    let fx = x - x0 as f64;
+    let fz = z - z0 as f64;
+    let at = |x: usize, z: usize| samples[z * width + x];
+    let top = at(x0, z0) * (1.0 - fx) + at(x0 + 1, z0) * fx;
+    let bottom = at(x0, z0 + 1) * (1.0 - fx) + at(x0 + 1, z0 + 1) * fx;
+    return top * (1.0 - fz) + bottom * fz;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_parser::load_engine_config;
+
+    #[test]
+    fn square_side_accepts_only_square_sample_counts() {
+        assert_eq!(square_side(4), Ok(2));
+        assert_eq!(square_side(1025 * 1025), Ok(1025));
+        assert!(square_side(6).is_err());
+        assert!(square_side(1025 * 1024).is_err());
+    }
+
+    #[test]
+    fn sample_bilinear_blends_the_surrounding_samples() {
+        // 3x2 samples, rising by 1 along x and by 10 along z
+        let samples = [0.0, 1.0, 2.0, 10.0, 11.0, 12.0];
+        assert_eq!(sample_bilinear(&samples, 3, 2, 0.0, 0.0), 0.0);
+        assert_eq!(sample_bilinear(&samples, 3, 2, 2.0, 1.0), 12.0);
+        assert_eq!(sample_bilinear(&samples, 3, 2, 1.5, 0.0), 1.5);
+        assert_eq!(sample_bilinear(&samples, 3, 2, 0.5, 0.5), 5.5);
+    }
+
+    #[test]
+    fn resample_stretches_the_samples_over_the_range() {
+        let samples = [100.0, 200.0, 300.0, 500.0];
+        let map = resample(&samples, 2, 2, 3, [-1.0, 1.0]);
+        assert_eq!(map.get_value(0, 0), -1.0);
+        assert_eq!(map.get_value(2, 2), 1.0);
+        assert_eq!(map.get_value(2, 0), -0.5);
+        // With the default range the lowest point of land only terrain lies below a sea level
+        // of 0, so such maps raise the low end
+        let map = resample(&samples, 2, 2, 3, [0.05, 1.0]);
+        assert_eq!(map.get_value(0, 0), 0.05);
+        assert_eq!(map.get_value(2, 2), 1.0);
+
+        let flat = resample(&[7.0; 4], 2, 2, 3, [0.2, 1.0]);
+        assert_eq!(flat.get_value(1, 1), 0.2);
+    }
+
+    #[test]
+    fn load_heightmap_reads_r32_files() {
+        let engine_config = EngineConfig {
+            world_size: 3,
+            ..load_engine_config()
+        };
+        let dir = std::env::temp_dir().join(format!("fok-heightmap-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let samples: [f32; 4] = [0.0, 1.0, 2.0, 4.0];
+        let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        fs::write(dir.join("square.r32"), &bytes).unwrap();
+        fs::write(dir.join("uneven.r32"), &bytes[..12]).unwrap();
+        fs::write(dir.join("heights.txt"), &bytes).unwrap();
+
+        let map = load_heightmap(&dir.join("square.r32"), &engine_config, [-1.0, 1.0]).unwrap();
+        assert_eq!(map.size(), (3, 3));
+        assert_eq!(map.get_value(0, 0), -1.0);
+        assert_eq!(map.get_value(2, 2), 1.0);
+        assert!(load_heightmap(&dir.join("uneven.r32"), &engine_config, [-1.0, 1.0]).is_err());
+        assert!(load_heightmap(&dir.join("heights.txt"), &engine_config, [-1.0, 1.0]).is_err());
+        assert!(load_heightmap(&dir.join("missing.r32"), &engine_config, [-1.0, 1.0]).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
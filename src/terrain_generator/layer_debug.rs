@@ -0,0 +1,178 @@
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+    tasks::{AsyncComputeTaskPool, Task},
+    utils::HashMap,
+};
+use futures_lite::future;
+
+use crate::config_parser;
+use crate::loading_screen::AppState;
+use crate::terrain_generator::noise_generator::{self, GenerationLayer};
+
+const PREVIEW_SIZE: f32 = 256.0;
+
+pub struct LayerDebugPlugin;
+
+impl Plugin for LayerDebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LayerDebug>().add_systems(
+            Update,
+            (
+                cycle_debug_layer,
+                handle_layer_preview_tasks,
+                show_layer_preview,
+            )
+                .chain()
+                .run_if(in_state(AppState::InGame)),
+        );
+    }
+}
+
+// The selected generation layer is sampled in the background the first time it is shown, then
+// kept so cycling through the layers again is instant.
+#[derive(Resource, Default)]
+pub struct LayerDebug {
+    pub layer: Option<GenerationLayer>,
+    previews: HashMap<GenerationLayer, Handle<Image>>,
+}
+
+#[derive(Component)]
+struct ComputeLayerComponent(GenerationLayer, Task<Image>);
+
+#[derive(Component)]
+struct LayerPreviewComponent;
+
+// L steps forward through the layers and Shift+L steps back, with an off state between the
+// last and the first layer.
+fn cycle_debug_layer(keys: Res<ButtonInput<KeyCode>>, mut layer_debug: ResMut<LayerDebug>) {
+    if !keys.just_pressed(KeyCode::KeyL) {
+        return;
+    }
+    let backwards = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let count = GenerationLayer::ALL.len();
+    let current = layer_debug
+        .layer
+        .and_then(|layer| GenerationLayer::ALL.iter().position(|l| *l == layer));
+    let next = match (current, backwards) {
+        (None, false) => Some(0),
+        (None, true) => Some(count - 1),
+        (Some(index), false) if index + 1 < count => Some(index + 1),
+        (Some(index), true) if index > 0 => Some(index - 1),
+        _ => None,
+    };
+    layer_debug.layer = next.map(|index| GenerationLayer::ALL[index]);
+}
+
+fn handle_layer_preview_tasks(
+    mut commands: Commands,
+    mut tasks: Query<(Entity, &mut ComputeLayerComponent)>,
+    mut images: ResMut<Assets<Image>>,
+    mut layer_debug: ResMut<LayerDebug>,
+) {
+    for (entity, mut task_component) in tasks.iter_mut() {
+        let future = future::block_on(future::poll_once(&mut task_component.1));
+        if let Some(image) = future {
+            layer_debug
+                .previews
+                .insert(task_component.0, images.add(image));
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn show_layer_preview(
+    mut commands: Commands,
+    layer_debug: Res<LayerDebug>,
+    map_config: Res<config_parser::MapConfig>,
+    engine_config: Res<config_parser::EngineConfig>,
+    tasks: Query<&ComputeLayerComponent>,
+    previews: Query<Entity, With<LayerPreviewComponent>>,
+) {
+    if !layer_debug.is_changed() {
+        return;
+    }
+    for entity in previews.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let Some(layer) = layer_debug.layer else {
+        return;
+    };
+
+    let preview = layer_debug.previews.get(&layer);
+    if preview.is_none() && !tasks.iter().any(|task| task.0 == layer) {
+        let thread_pool = AsyncComputeTaskPool::get();
+        let m_config = map_config.clone();
+        let e_config = engine_config.clone();
+        let task = thread_pool.spawn(async move {
+            let map = noise_generator::generate_layer(&m_config, &e_config, layer);
+            return render_preview_image(layer, &map);
+        });
+        commands.spawn(ComputeLayerComponent(layer, task));
+    }
+
+    let caption = match preview {
+        Some(_) => layer.name().to_string(),
+        None => format!("{} (generating)", layer.name()),
+    };
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    right: Val::Px(10.0),
+                    bottom: Val::Px(10.0),
+                    flex_direction: FlexDirection::Column,
+                    ..default()
+                },
+                ..default()
+            },
+            LayerPreviewComponent,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                caption,
+                TextStyle {
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ));
+            if let Some(preview) = preview {
+                parent.spawn(ImageBundle {
+                    style: Style {
+                        width: Val::Px(PREVIEW_SIZE),
+                        height: Val::Px(PREVIEW_SIZE),
+                        ..default()
+                    },
+                    image: UiImage::new(preview.clone()),
+                    ..default()
+                });
+            }
+        });
+}
+
+pub(super) fn render_preview_image(layer: GenerationLayer, map: &noise::utils::NoiseMap) -> Image {
+    let noise_image = noise_generator::render_layer(layer, map);
+    let (width, height) = noise_image.size();
+    return Image::new(
+        Extent3d {
+            width: width as u32,
+            height: height as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        noise_image.iter().flatten().copied().collect(),
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+}
+
+pub fn dump_layers_headless() {
+    let map_config = config_parser::load_map_config();
+    let engine_config = config_parser::load_engine_config();
+    noise_generator::dump_layers(&map_config, &engine_config);
+    println!("Wrote {} generation layers.", GenerationLayer::ALL.len());
+}
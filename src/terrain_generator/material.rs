@@ -10,3 +10,13 @@ impl MaterialExtension for terrain_generator::TerrainMaterial {
         "shaders/terrain.wgsl".into()
     }
 }
+
+impl MaterialExtension for terrain_generator::WaterMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/water.wgsl".into()
+    }
+
+    fn deferred_fragment_shader() -> ShaderRef {
+        "shaders/water.wgsl".into()
+    }
+}
@@ -1,3 +1,5 @@
+use std::f32::consts::PI;
+
 use bevy::prelude::*;
 use bevy::render::{
     mesh::{Indices, VertexAttributeValues},
@@ -5,144 +7,231 @@ use bevy::render::{
     render_resource::PrimitiveTopology,
 };
 
-use crate::config_parser::EngineConfig;
+use bevy_rapier3d::prelude::*;
+
+use crate::config_parser::{EngineConfig, TerrainColliderType};
+use crate::terrain_generator::chunk::ChunkCoord;
+use crate::terrain_generator::TerrainMeshes;
 
 pub fn generate_low_poly_terrain(
     engine_config: EngineConfig,
+    sea_level: f64,
     map: Vec<f64>,
-) -> (Vec<Mesh>, Vec<(Vec<Vec3>, Vec<[u32; 3]>)>) {
+) -> TerrainMeshes {
     validate_engine_config(&engine_config);
     let mut meshes = Vec::new();
     let mut colliders = Vec::new();
+    let mut water = Vec::new();
     let flattened_map: Vec<Vec<f64>> = map
         .chunks(engine_config.world_size as usize)
         .map(|chunk| chunk.to_vec())
         .collect();
-    let chunk_ratio = (engine_config.world_size / engine_config.chunk_size) + 1;
-
-    for i in 0..chunk_ratio {
-        for j in 0..chunk_ratio {
-            let mut collider_indices = compute_collider_indices(&engine_config);
-            let mut collider_vertices = compute_collider_vertices(&engine_config, &map, &i, &j);
-
-            let mut indices = Vec::new();
-            let mut vertices = Vec::new();
-            let mut normals = Vec::new();
-            let mut colors = Vec::new();
-
-            let x_start = (i * engine_config.chunk_size).saturating_sub(i) as usize;
-            let z_start = (j * engine_config.chunk_size).saturating_sub(j) as usize;
-            let x_end = x_start + engine_config.chunk_size as usize;
-            let z_end = z_start + engine_config.chunk_size as usize;
-
-            for z in z_start..z_end - 1 {
-                for x in x_start..x_end - 1 {
-                    let y_top_left = flattened_map[z][x] as f32 * engine_config.world_height;
-                    let y_top_right = flattened_map[z][x + 1] as f32 * engine_config.world_height;
-                    let y_bottom_left = flattened_map[z + 1][x] as f32 * engine_config.world_height;
-                    let y_bottom_right =
-                        flattened_map[z + 1][x + 1] as f32 * engine_config.world_height;
-
-                    // Define vertices for the first triangle (top-left, bottom-left, bottom-right)
-                    let base_index = vertices.len() as u32;
-                    vertices.push([
-                        x as f32 - x_start as f32,
-                        y_top_left,
-                        z as f32 - z_start as f32,
-                    ]);
-                    vertices.push([
-                        x as f32 - x_start as f32,
-                        y_bottom_left,
-                        (z + 1) as f32 - z_start as f32,
-                    ]);
-                    vertices.push([
-                        (x + 1) as f32 - x_start as f32,
-                        y_bottom_right,
-                        (z + 1) as f32 - z_start as f32,
-                    ]);
-
-                    indices.extend_from_slice(&[base_index, base_index + 1, base_index + 2]);
-                    let avg_y1 = (y_top_left + y_bottom_left + y_bottom_right) / 3.0;
-                    let triangle_color1: [f32; 4] = match avg_y1 {
-                        avg_y1 if avg_y1 < -0.3 => [0.829, 0.806, 0.567, 1.0],
-                        avg_y1 if avg_y1 >= -0.3 && avg_y1 < 0.5 => [0.625, 0.96, 0.559, 1.0],
-                        _ => [0.5, 0.5, 0.5, 1.0],
-                    };
-                    for _i in 0..3 {
-                        colors.push(triangle_color1);
-                    }
 
-                    let normal1 =
-                        calculate_normal(&vertices, [base_index, base_index + 1, base_index + 2]);
-                    normals.extend_from_slice(&[normal1, normal1, normal1]);
-
-                    // Define vertices for the second triangle (top-left, bottom-right, top-right)
-                    vertices.push([
-                        x as f32 - x_start as f32,
-                        y_top_left,
-                        z as f32 - z_start as f32,
-                    ]);
-                    vertices.push([
-                        (x + 1) as f32 - x_start as f32,
-                        y_bottom_right,
-                        (z + 1) as f32 - z_start as f32,
-                    ]);
-                    vertices.push([
-                        (x + 1) as f32 - x_start as f32,
-                        y_top_right,
-                        z as f32 - z_start as f32,
-                    ]);
-
-                    indices.extend_from_slice(&[base_index + 3, base_index + 4, base_index + 5]);
-                    let avg_y2 = (y_top_left + y_bottom_right + y_top_right) / 3.0;
-                    let triangle_color2: [f32; 4] = match avg_y2 {
-                        avg_y2 if avg_y2 < -0.3 => [0.829, 0.806, 0.567, 1.0],
-                        avg_y2 if avg_y2 >= -0.3 && avg_y2 < 0.5 => [0.625, 0.96, 0.559, 1.0],
-                        _ => [0.5, 0.5, 0.5, 1.0],
-                    };
-                    for _i in 0..3 {
-                        colors.push(triangle_color2);
-                    }
+    for chunk in ChunkCoord::iter(&engine_config) {
+        let mut indices = Vec::new();
+        let mut vertices = Vec::new();
+        let mut normals = Vec::new();
 
-                    let normal2 = calculate_normal(
-                        &vertices,
-                        [base_index + 3, base_index + 4, base_index + 5],
-                    );
-                    normals.extend_from_slice(&[normal2, normal2, normal2]);
-                }
+        let (x_start, z_start) = chunk.grid_origin(&engine_config);
+        let (width, depth) = chunk.grid_size(&engine_config);
+        let x_end = x_start + width;
+        let z_end = z_start + depth;
+
+        for z in z_start..z_end - 1 {
+            for x in x_start..x_end - 1 {
+                let y_top_left = flattened_map[z][x] as f32 * engine_config.world_height;
+                let y_top_right = flattened_map[z][x + 1] as f32 * engine_config.world_height;
+                let y_bottom_left = flattened_map[z + 1][x] as f32 * engine_config.world_height;
+                let y_bottom_right =
+                    flattened_map[z + 1][x + 1] as f32 * engine_config.world_height;
+
+                // Define vertices for the first triangle (top-left, bottom-left, bottom-right)
+                let base_index = vertices.len() as u32;
+                vertices.push([
+                    x as f32 - x_start as f32,
+                    y_top_left,
+                    z as f32 - z_start as f32,
+                ]);
+                vertices.push([
+                    x as f32 - x_start as f32,
+                    y_bottom_left,
+                    (z + 1) as f32 - z_start as f32,
+                ]);
+                vertices.push([
+                    (x + 1) as f32 - x_start as f32,
+                    y_bottom_right,
+                    (z + 1) as f32 - z_start as f32,
+                ]);
+
+                indices.extend_from_slice(&[base_index, base_index + 1, base_index + 2]);
+                let normal1 =
+                    calculate_normal(&vertices, [base_index, base_index + 1, base_index + 2]);
+                normals.extend_from_slice(&[normal1, normal1, normal1]);
+
+                // Define vertices for the second triangle (top-left, bottom-right, top-right)
+                vertices.push([
+                    x as f32 - x_start as f32,
+                    y_top_left,
+                    z as f32 - z_start as f32,
+                ]);
+                vertices.push([
+                    (x + 1) as f32 - x_start as f32,
+                    y_bottom_right,
+                    (z + 1) as f32 - z_start as f32,
+                ]);
+                vertices.push([
+                    (x + 1) as f32 - x_start as f32,
+                    y_top_right,
+                    z as f32 - z_start as f32,
+                ]);
+
+                indices.extend_from_slice(&[base_index + 3, base_index + 4, base_index + 5]);
+                let normal2 =
+                    calculate_normal(&vertices, [base_index + 3, base_index + 4, base_index + 5]);
+                normals.extend_from_slice(&[normal2, normal2, normal2]);
             }
+        }
+
+        let mut mesh = Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD,
+        );
+        mesh.insert_indices(Indices::U32(indices.clone()));
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vertices.clone());
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals.clone());
+
+        meshes.push(mesh);
+        colliders.push(compute_collider(&engine_config, &map, &chunk));
+        water.push(compute_water_mesh(&engine_config, sea_level, &map, &chunk));
+    }
+
+    return TerrainMeshes {
+        terrain: meshes,
+        colliders,
+        water,
+    };
+}
+
+// Builds a flat surface at sea level over every cell with a corner below it, which covers both
+// the sea and any basins that dip under sea level inland. The first uv channel holds how deep
+// the terrain lies under each vertex so the water material can tint by depth.
+fn compute_water_mesh(
+    engine_config: &EngineConfig,
+    sea_level: f64,
+    map: &Vec<f64>,
+    chunk: &ChunkCoord,
+) -> Option<Mesh> {
+    let (x_start, z_start) = chunk.grid_origin(engine_config);
+    let (width, depth) = chunk.grid_size(engine_config);
+    let water_height = sea_level as f32 * engine_config.world_height;
+    let height = |x: usize, z: usize| {
+        map[(z_start + z) * engine_config.world_size + (x_start + x)] as f32
+            * engine_config.world_height
+    };
 
-            let mut mesh = Mesh::new(
-                PrimitiveTopology::TriangleList,
-                RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD,
-            );
-            mesh.insert_indices(Indices::U32(indices.clone()));
-            mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vertices.clone());
-            mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals.clone());
-            mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors.clone());
-
-            meshes.push(mesh);
-            colliders.push((collider_vertices, collider_indices));
+    let mut indices = Vec::new();
+    let mut vertices = Vec::new();
+    let mut uvs = Vec::new();
+    for z in 0..depth - 1 {
+        for x in 0..width - 1 {
+            let corners = [(x, z), (x, z + 1), (x + 1, z + 1), (x + 1, z)];
+            if corners.iter().all(|(x, z)| height(*x, *z) >= water_height) {
+                continue;
+            }
+            let base_index = vertices.len() as u32;
+            for (x, z) in corners {
+                vertices.push([x as f32, water_height, z as f32]);
+                uvs.push([(water_height - height(x, z)).max(0.0), 0.0]);
+            }
+            indices.extend_from_slice(&[
+                base_index,
+                base_index + 1,
+                base_index + 2,
+                base_index,
+                base_index + 2,
+                base_index + 3,
+            ]);
         }
     }
+    if vertices.is_empty() {
+        return None;
+    }
 
-    return (meshes, colliders);
+    let normals = vec![[0.0, 1.0, 0.0]; vertices.len()];
+    let mut mesh = Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD,
+    );
+    mesh.insert_indices(Indices::U32(indices));
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vertices);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    return Some(mesh);
+}
+
+fn compute_collider(
+    engine_config: &EngineConfig,
+    map: &Vec<f64>,
+    chunk: &ChunkCoord,
+) -> (Collider, Transform) {
+    match engine_config.terrain_collider {
+        TerrainColliderType::Heightfield => compute_heightfield_collider(engine_config, map, chunk),
+        TerrainColliderType::Trimesh => (
+            Collider::trimesh(
+                compute_collider_vertices(engine_config, map, chunk),
+                compute_collider_indices(engine_config, chunk),
+            ),
+            Transform::IDENTITY,
+        ),
+    }
+}
+
+// Rapier centres heightfields on their origin and splits each cell along the opposite diagonal
+// to the render mesh, so the field is turned a quarter around Y to make the triangles line up.
+// After the turn its rows run along the chunk's x axis and its columns run backwards along z.
+fn compute_heightfield_collider(
+    engine_config: &EngineConfig,
+    map: &Vec<f64>,
+    chunk: &ChunkCoord,
+) -> (Collider, Transform) {
+    let (x_start, z_start) = chunk.grid_origin(engine_config);
+    let (width, depth) = chunk.grid_size(engine_config);
+    let mut heights = Vec::with_capacity(width * depth);
+    for column in 0..depth {
+        let z = z_start + depth - 1 - column;
+        for x in x_start..x_start + width {
+            heights.push(map[z * engine_config.world_size + x] as f32);
+        }
+    }
+    let collider = Collider::heightfield(
+        heights,
+        width,
+        depth,
+        Vec3::new(
+            (depth - 1) as f32,
+            engine_config.world_height,
+            (width - 1) as f32,
+        ),
+    );
+    let transform = Transform {
+        translation: Vec3::new((width - 1) as f32 / 2.0, 0.0, (depth - 1) as f32 / 2.0),
+        rotation: Quat::from_rotation_y(PI / 2.0),
+        ..default()
+    };
+    return (collider, transform);
 }
 
 fn compute_collider_vertices(
     engine_config: &EngineConfig,
     map: &Vec<f64>,
-    i: &usize,
-    j: &usize,
+    chunk: &ChunkCoord,
 ) -> Vec<Vec3> {
     let mut vertices = Vec::new();
-    for z in 0..engine_config.chunk_size {
-        for x in 0..engine_config.chunk_size {
-            let index = (((j * (engine_config.world_size / engine_config.chunk_size)) + x)
-                + ((i * (engine_config.world_size / engine_config.chunk_size)) + z)
-                    * engine_config.world_size)
-                - (i * engine_config.world_size)
-                - j;
+    let (x_start, z_start) = chunk.grid_origin(engine_config);
+    let (width, depth) = chunk.grid_size(engine_config);
+    for z in 0..depth {
+        for x in 0..width {
+            let index = (z_start + z) * engine_config.world_size + (x_start + x);
             let y = map[index] as f32 * engine_config.world_height;
             vertices.push([x as f32, y, z as f32]);
         }
@@ -151,22 +240,23 @@ fn compute_collider_vertices(
     return collider_vertices;
 }
 
-fn compute_collider_indices(engine_config: &EngineConfig) -> Vec<[u32; 3]> {
+fn compute_collider_indices(engine_config: &EngineConfig, chunk: &ChunkCoord) -> Vec<[u32; 3]> {
     let mut indices = Vec::new();
-    for y in 0..(engine_config.chunk_size - 1) {
-        for x in 0..(engine_config.chunk_size - 1) {
-            let top_left = y * engine_config.chunk_size + x;
-            let top_right = y * engine_config.chunk_size + x + 1;
-            let bottom_left = (y + 1) * engine_config.chunk_size + x;
-            let bottom_right = (y + 1) * engine_config.chunk_size + x + 1;
+    let (width, depth) = chunk.grid_size(engine_config);
+    for y in 0..(depth - 1) {
+        for x in 0..(width - 1) {
+            let top_left = y * width + x;
+            let top_right = y * width + x + 1;
+            let bottom_left = (y + 1) * width + x;
+            let bottom_right = (y + 1) * width + x + 1;
 
             indices.push(top_left as u32);
             indices.push(bottom_left as u32);
-            indices.push(top_right as u32);
+            indices.push(bottom_right as u32);
 
-            indices.push(top_right as u32);
-            indices.push(bottom_left as u32);
+            indices.push(top_left as u32);
             indices.push(bottom_right as u32);
+            indices.push(top_right as u32);
         }
     }
     let collider_indices = indices
@@ -177,9 +267,9 @@ fn compute_collider_indices(engine_config: &EngineConfig) -> Vec<[u32; 3]> {
 }
 
 fn validate_engine_config(engine_config: &EngineConfig) {
-    if engine_config.world_size % engine_config.chunk_size != 0 {
+    if engine_config.chunk_size < 2 || engine_config.world_size < 2 {
         panic!(
-            "Chunk size {:?} must be a factor of world size {:?}",
+            "Chunk size {:?} and world size {:?} must both be at least 2",
             engine_config.chunk_size, engine_config.world_size
         );
     }
@@ -206,3 +296,173 @@ fn calculate_normal(vertices: &Vec<[f32; 3]>, indices: [u32; 3]) -> [f32; 3] {
         normal
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::config_parser::load_engine_config;
+
+    const SIZES: [(usize, usize); 6] = [(7, 7), (9, 8), (10, 4), (17, 5), (20, 6), (33, 8)];
+
+    fn engine_config(
+        world_size: usize,
+        chunk_size: usize,
+        terrain_collider: TerrainColliderType,
+    ) -> EngineConfig {
+        return EngineConfig {
+            world_size,
+            chunk_size,
+            terrain_collider,
+            ..load_engine_config()
+        };
+    }
+
+    // A different height at every grid point, so a vertex read from the wrong row shows up
+    fn test_map(world_size: usize) -> Vec<f64> {
+        return (0..world_size * world_size)
+            .map(|index| ((index * 37) % 101) as f64 / 101.0)
+            .collect();
+    }
+
+    #[test]
+    fn neighbouring_chunks_share_border_heights() {
+        for (world_size, chunk_size) in SIZES {
+            let config = engine_config(world_size, chunk_size, TerrainColliderType::Trimesh);
+            let map = test_map(world_size);
+            let meshes = generate_low_poly_terrain(config.clone(), 0.0, map.clone());
+            assert_eq!(meshes.terrain.len(), ChunkCoord::iter(&config).count());
+
+            // Grid point -> the chunks with a vertex there and the height they gave it
+            let mut heights: HashMap<(usize, usize), Vec<(ChunkCoord, f32)>> = HashMap::new();
+            for chunk in ChunkCoord::iter(&config) {
+                let mesh = &meshes.terrain[chunk.index(&config)];
+                let Some(VertexAttributeValues::Float32x3(positions)) =
+                    mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+                else {
+                    panic!("{:?} has no positions", chunk);
+                };
+                let (width, depth) = chunk.grid_size(&config);
+                assert_eq!(positions.len(), (width - 1) * (depth - 1) * 6);
+
+                let origin = chunk.world_origin(&config);
+                for position in positions {
+                    let x = (origin.x + position[0]) as usize;
+                    let z = (origin.z + position[2]) as usize;
+                    assert!(
+                        x < world_size && z < world_size,
+                        "{:?} reads past the map",
+                        chunk
+                    );
+                    let entry = heights.entry((x, z)).or_default();
+                    if !entry.iter().any(|(other, _)| *other == chunk) {
+                        entry.push((chunk, position[1]));
+                    }
+                }
+            }
+
+            assert_eq!(heights.len(), world_size * world_size);
+            for ((x, z), chunks) in heights {
+                let expected = map[z * world_size + x] as f32 * config.world_height;
+                for (chunk, height) in &chunks {
+                    assert_eq!(*height, expected, "({}, {}) in {:?}", x, z, chunk);
+                }
+                // Vertices on a chunk border are in every chunk that touches it
+                let stride = ChunkCoord::stride(&config);
+                let on_border = |v: usize| v % stride == 0 && v > 0 && v < world_size - 1;
+                let expected_chunks = (1 + on_border(x) as usize) * (1 + on_border(z) as usize);
+                assert_eq!(chunks.len(), expected_chunks, "({}, {})", x, z);
+            }
+        }
+    }
+
+    // Height of the render mesh at a point inside a chunk, split along the same diagonal as
+    // generate_low_poly_terrain
+    fn render_height(
+        config: &EngineConfig,
+        map: &[f64],
+        chunk: &ChunkCoord,
+        x: f32,
+        z: f32,
+    ) -> f32 {
+        let (x_start, z_start) = chunk.grid_origin(config);
+        let (width, depth) = chunk.grid_size(config);
+        let cell_x = (x.floor() as usize).min(width - 2);
+        let cell_z = (z.floor() as usize).min(depth - 2);
+        let (u, v) = (x - cell_x as f32, z - cell_z as f32);
+        let height = |dx: usize, dz: usize| {
+            let index = (z_start + cell_z + dz) * config.world_size + x_start + cell_x + dx;
+            map[index] as f32 * config.world_height
+        };
+        let (top_left, top_right) = (height(0, 0), height(1, 0));
+        let (bottom_left, bottom_right) = (height(0, 1), height(1, 1));
+        if v >= u {
+            return top_left + u * (bottom_right - bottom_left) + v * (bottom_left - top_left);
+        }
+        return top_left + u * (top_right - top_left) + v * (bottom_right - top_right);
+    }
+
+    #[test]
+    fn collider_heights_match_the_render_mesh() {
+        const CAST_HEIGHT: f32 = 100.0;
+        // Keeps rays on grid points just inside the chunk, off its outer edges
+        const INSET: f32 = 1e-3;
+        for terrain_collider in [
+            TerrainColliderType::Heightfield,
+            TerrainColliderType::Trimesh,
+        ] {
+            for (world_size, chunk_size) in [(9, 8), (10, 4), (20, 6)] {
+                let config = engine_config(world_size, chunk_size, terrain_collider);
+                let map = test_map(world_size);
+                for chunk in ChunkCoord::iter(&config) {
+                    let (collider, transform) = compute_collider(&config, &map, &chunk);
+                    let (width, depth) = chunk.grid_size(&config);
+                    let (max_x, max_z) = ((width - 1) as f32, (depth - 1) as f32);
+
+                    // Every grid point, then a point either side of each cell's diagonal
+                    let mut points = Vec::new();
+                    for z in 0..depth {
+                        for x in 0..width {
+                            points.push((x as f32, z as f32));
+                            if x + 1 < width && z + 1 < depth {
+                                points.push((x as f32 + 0.25, z as f32 + 0.7));
+                                points.push((x as f32 + 0.7, z as f32 + 0.25));
+                            }
+                        }
+                    }
+
+                    for (x, z) in points {
+                        let (x, z) = (x.clamp(INSET, max_x - INSET), z.clamp(INSET, max_z - INSET));
+                        let expected = render_height(&config, &map, &chunk, x, z);
+                        let time_of_impact = collider.cast_ray(
+                            transform.translation,
+                            transform.rotation,
+                            Vec3::new(x, CAST_HEIGHT, z),
+                            Vec3::NEG_Y,
+                            CAST_HEIGHT * 2.0,
+                            true,
+                        );
+                        let Some(time_of_impact) = time_of_impact else {
+                            panic!(
+                                "{:?} ray at ({}, {}) missed {:?}",
+                                terrain_collider, x, z, chunk
+                            );
+                        };
+                        let height = CAST_HEIGHT - time_of_impact;
+                        assert!(
+                            (height - expected).abs() < 0.02,
+                            "{:?} {:?} at ({}, {}): hit {} but the mesh is at {}",
+                            terrain_collider,
+                            chunk,
+                            x,
+                            z,
+                            height,
+                            expected
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
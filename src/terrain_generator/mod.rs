@@ -1,29 +1,207 @@
 use bevy::{prelude::*, render::render_resource::AsBindGroup};
+use bevy_rapier3d::prelude::Collider;
 
+mod chunk;
+mod export;
+mod heightmap_import;
+mod layer_debug;
 mod material;
 mod mesh_generator;
 mod noise_generator;
+mod overlay;
+mod query;
 use noise::utils::NoiseMap;
 
+pub use chunk::ChunkCoord;
+pub use export::{export_headless, ExportFormat, TerrainExportPlugin};
+pub use layer_debug::{dump_layers_headless, LayerDebugPlugin};
+pub use overlay::{MapOverlay, MapOverlayPlugin};
+pub use query::TerrainQuery;
+
 use crate::config_parser::*;
 
+// Colors the terrain by height and slope. Heights are in world units, so the config values
+// are scaled by the world height, and slopes are angles from the horizontal in radians.
+// The overlay fields are driven by the `MapOverlay` resource.
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+pub struct TerrainMaterial {
+    #[uniform(100)]
+    pub sand_color: LinearRgba,
+    #[uniform(100)]
+    pub grass_color: LinearRgba,
+    #[uniform(100)]
+    pub rock_color: LinearRgba,
+    #[uniform(100)]
+    pub snow_color: LinearRgba,
+    #[uniform(100)]
+    pub sea_level: f32,
+    #[uniform(100)]
+    pub beach_height: f32,
+    #[uniform(100)]
+    pub snow_line: f32,
+    #[uniform(100)]
+    pub world_height: f32,
+    #[uniform(100)]
+    pub rock_slope_start: f32,
+    #[uniform(100)]
+    pub rock_slope_end: f32,
+    #[uniform(100)]
+    pub overlay_mode: u32,
+    #[uniform(100)]
+    pub contour_interval: f32,
+    // Number of heightmap cells along each axis, used to map world positions onto the
+    // overlay texture
+    #[uniform(100)]
+    pub cell_count: f32,
+    #[texture(101)]
+    #[sampler(102)]
+    pub overlay_texture: Option<Handle<Image>>,
+}
+
+impl TerrainMaterial {
+    pub fn new(map_config: &MapConfig, engine_config: &EngineConfig) -> Self {
+        TerrainMaterial {
+            sand_color: LinearRgba::new(0.829, 0.806, 0.567, 1.0),
+            grass_color: LinearRgba::new(0.625, 0.96, 0.559, 1.0),
+            rock_color: LinearRgba::new(0.5, 0.5, 0.5, 1.0),
+            snow_color: LinearRgba::new(0.95, 0.97, 1.0, 1.0),
+            sea_level: map_config.sea_level as f32 * engine_config.world_height,
+            beach_height: map_config.beach_height as f32 * engine_config.world_height,
+            snow_line: map_config.snow_line as f32 * engine_config.world_height,
+            world_height: engine_config.world_height,
+            rock_slope_start: (map_config.rock_slope_start as f32).to_radians(),
+            rock_slope_end: (map_config.rock_slope_end as f32).to_radians(),
+            overlay_mode: 0,
+            contour_interval: engine_config.contour_interval,
+            cell_count: (engine_config.world_size - 1) as f32,
+            overlay_texture: None,
+        }
+    }
+
+    // Mirrors `terrain_color` in terrain.wgsl for things drawn outside the shader, like exports.
+    pub fn color_at(&self, height: f32, normal: Vec3) -> LinearRgba {
+        let slope = normal.normalize().y.clamp(-1.0, 1.0).acos();
+        let blend = 0.05 * self.world_height;
+        let beach = self.sea_level + self.beach_height;
+        let mut color = self.sand_color.mix(
+            &self.grass_color,
+            smoothstep(beach - blend, beach + blend, height),
+        );
+        color = color.mix(
+            &self.rock_color,
+            smoothstep(self.rock_slope_start, self.rock_slope_end, slope),
+        );
+        color = color.mix(
+            &self.snow_color,
+            smoothstep(self.snow_line - blend, self.snow_line + blend, height),
+        );
+        return color;
+    }
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    return t * t * (3.0 - 2.0 * t);
+}
+
 #[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
-pub struct TerrainMaterial {}
+pub struct WaterMaterial {
+    #[uniform(100)]
+    pub shallow_color: LinearRgba,
+    #[uniform(100)]
+    pub deep_color: LinearRgba,
+    // Water depth, in world units, at which the deep color is fully reached
+    #[uniform(100)]
+    pub max_depth: f32,
+    #[uniform(100)]
+    pub wave_speed: f32,
+    #[uniform(100)]
+    pub wave_scale: f32,
+}
+
+impl Default for WaterMaterial {
+    fn default() -> Self {
+        WaterMaterial {
+            shallow_color: LinearRgba::new(0.18, 0.62, 0.68, 0.55),
+            deep_color: LinearRgba::new(0.02, 0.12, 0.3, 0.9),
+            max_depth: 2.0,
+            wave_speed: 1.2,
+            wave_scale: 1.5,
+        }
+    }
+}
+
+// Meshes are indexed by `ChunkCoord::index`. Chunks without any water have no water mesh.
+pub struct TerrainMeshes {
+    pub terrain: Vec<Mesh>,
+    pub colliders: Vec<(Collider, Transform)>,
+    pub water: Vec<Option<Mesh>>,
+}
 
 #[derive(Resource)]
 pub struct TerrainMap {
     pub map: NoiseMap,
 }
 
+impl TerrainMap {
+    // Colored with the same gradient as the preview written by `generate_texture`, shifted so
+    // the coastline falls on the configured sea level
+    pub fn preview_image(&self, map_config: &MapConfig) -> Image {
+        let (width, height) = self.map.size();
+        let mut shifted = NoiseMap::new(width, height);
+        for z in 0..height {
+            for x in 0..width {
+                shifted.set_value(x, z, self.map.get_value(x, z) - map_config.sea_level);
+            }
+        }
+        return layer_debug::render_preview_image(
+            noise_generator::GenerationLayer::Final,
+            &shifted,
+        );
+    }
+}
+
 pub async fn create_texture_map(map_config: MapConfig, engine_config: EngineConfig) -> NoiseMap {
+    if let Some(heightmap_path) = &map_config.heightmap_path {
+        let path = std::path::Path::new(heightmap_path);
+        let range = map_config.heightmap_range.unwrap_or([-1.0, 1.0]);
+        match heightmap_import::load_heightmap(path, &engine_config, range) {
+            Ok(map) => return map,
+            Err(e) => error!(
+                "Could not import heightmap {:?}, generating the terrain instead: {}",
+                path, e
+            ),
+        }
+    }
     let map: NoiseMap = noise_generator::generate_texture(&map_config, &engine_config);
     return map;
 }
 
 pub async fn create_map_mesh(
     engine_config: EngineConfig,
+    sea_level: f64,
     map: Vec<f64>,
-) -> (Vec<Mesh>, Vec<(Vec<Vec3>, Vec<[u32; 3]>)>) {
-    let (meshes, colliders) = mesh_generator::generate_low_poly_terrain(engine_config, map);
-    return (meshes, colliders);
+) -> TerrainMeshes {
+    let meshes = mesh_generator::generate_low_poly_terrain(engine_config, sea_level, map);
+    return meshes;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_heightmap_falls_back_to_noise() {
+        let map_config = MapConfig {
+            heightmap_path: Some("assets/heightmaps/missing.png".to_string()),
+            ..load_map_config()
+        };
+        let engine_config = EngineConfig {
+            world_size: 33,
+            chunk_size: 8,
+            ..load_engine_config()
+        };
+        let map = futures_lite::future::block_on(create_texture_map(map_config, engine_config));
+        assert_eq!(map.size(), (33, 33));
+    }
 }
@@ -7,8 +7,96 @@ use noise::{core::worley::ReturnType, utils::*, *};
 
 use crate::config_parser::*;
 
-#[allow(non_snake_case)]
+// Named intermediate modules of the generator, in the order they are built
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GenerationLayer {
+    ContinentDef,
+    TerrainTypeDef,
+    MountainBaseDef,
+    MountainousHigh,
+    MountainousLow,
+    MountainousTerrain,
+    HillyTerrain,
+    PlainsTerrain,
+    BadlandsSand,
+    BadlandsCliffs,
+    BadlandsTerrain,
+    RiverPositions,
+    ContinentalShelf,
+    BaseContinentElev,
+    ContinentsWithPlains,
+    ContinentsWithHills,
+    ContinentsWithMountains,
+    ContinentsWithBadlands,
+    Final,
+}
+
+impl GenerationLayer {
+    pub const ALL: [GenerationLayer; 19] = [
+        GenerationLayer::ContinentDef,
+        GenerationLayer::TerrainTypeDef,
+        GenerationLayer::MountainBaseDef,
+        GenerationLayer::MountainousHigh,
+        GenerationLayer::MountainousLow,
+        GenerationLayer::MountainousTerrain,
+        GenerationLayer::HillyTerrain,
+        GenerationLayer::PlainsTerrain,
+        GenerationLayer::BadlandsSand,
+        GenerationLayer::BadlandsCliffs,
+        GenerationLayer::BadlandsTerrain,
+        GenerationLayer::RiverPositions,
+        GenerationLayer::ContinentalShelf,
+        GenerationLayer::BaseContinentElev,
+        GenerationLayer::ContinentsWithPlains,
+        GenerationLayer::ContinentsWithHills,
+        GenerationLayer::ContinentsWithMountains,
+        GenerationLayer::ContinentsWithBadlands,
+        GenerationLayer::Final,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GenerationLayer::ContinentDef => "continent_def",
+            GenerationLayer::TerrainTypeDef => "terrain_type_def",
+            GenerationLayer::MountainBaseDef => "mountain_base_def",
+            GenerationLayer::MountainousHigh => "mountainous_high",
+            GenerationLayer::MountainousLow => "mountainous_low",
+            GenerationLayer::MountainousTerrain => "mountainous_terrain",
+            GenerationLayer::HillyTerrain => "hilly_terrain",
+            GenerationLayer::PlainsTerrain => "plains_terrain",
+            GenerationLayer::BadlandsSand => "badlands_sand",
+            GenerationLayer::BadlandsCliffs => "badlands_cliffs",
+            GenerationLayer::BadlandsTerrain => "badlands_terrain",
+            GenerationLayer::RiverPositions => "river_positions",
+            GenerationLayer::ContinentalShelf => "continental_shelf",
+            GenerationLayer::BaseContinentElev => "base_continent_elev",
+            GenerationLayer::ContinentsWithPlains => "continents_with_plains",
+            GenerationLayer::ContinentsWithHills => "continents_with_hills",
+            GenerationLayer::ContinentsWithMountains => "continents_with_mountains",
+            GenerationLayer::ContinentsWithBadlands => "continents_with_badlands",
+            GenerationLayer::Final => "final",
+        }
+    }
+}
+
 pub fn generate_texture(map_config: &MapConfig, engine_config: &EngineConfig) -> NoiseMap {
+    let noise_map = generate_layer(map_config, engine_config, GenerationLayer::Final);
+
+    write_image_to_file(
+        &render_layer(GenerationLayer::Final, &noise_map),
+        "fbm_perlin.png",
+    );
+
+    return noise_map;
+}
+
+// Builds the whole module graph, then samples only the requested layer
+#[allow(non_snake_case)]
+pub fn generate_layer(
+    map_config: &MapConfig,
+    engine_config: &EngineConfig,
+    layer: GenerationLayer,
+) -> NoiseMap {
     fn baseContinentDef(map_config: &MapConfig) -> impl NoiseFn<f64, 3> {
         let baseContinentDef_fb0 = Fbm::<Perlin>::new(map_config.seed)
             .set_frequency(map_config.continent_frequency)
@@ -170,11 +258,11 @@ pub fn generate_texture(map_config: &MapConfig, engine_config: &EngineConfig) ->
 
     let mountainousLow = Cache::new(mountainousLow_mu);
 
-    let mountainousTerrain_sb0 = ScaleBias::new(mountainousLow)
+    let mountainousTerrain_sb0 = ScaleBias::new(&mountainousLow)
         .set_scale(0.03125)
         .set_bias(-0.96875);
 
-    let mountainousTerrain_sb1 = ScaleBias::new(mountainousHigh)
+    let mountainousTerrain_sb1 = ScaleBias::new(&mountainousHigh)
         .set_scale(0.25)
         .set_bias(0.25);
 
@@ -326,9 +414,11 @@ pub fn generate_texture(map_config: &MapConfig, engine_config: &EngineConfig) ->
 
     let badlandsCliffs = Cache::new(badlandsCliffs_tu1);
 
-    let badlandsTerrain_sb = ScaleBias::new(badlandsSand).set_scale(0.25).set_bias(-0.75);
+    let badlandsTerrain_sb = ScaleBias::new(&badlandsSand)
+        .set_scale(0.25)
+        .set_bias(-0.75);
 
-    let badlandsTerrain_ma = Max::new(badlandsCliffs, badlandsTerrain_sb);
+    let badlandsTerrain_ma = Max::new(&badlandsCliffs, badlandsTerrain_sb);
 
     let badlandsTerrain = Cache::new(badlandsTerrain_ma);
 
@@ -368,7 +458,7 @@ pub fn generate_texture(map_config: &MapConfig, engine_config: &EngineConfig) ->
 
     let riverPositions = Cache::new(riverPositions_tu);
 
-    let scaledMountainousTerrain_sb0 = ScaleBias::new(mountainousTerrain)
+    let scaledMountainousTerrain_sb0 = ScaleBias::new(&mountainousTerrain)
         .set_scale(0.125)
         .set_bias(0.125);
 
@@ -389,7 +479,7 @@ pub fn generate_texture(map_config: &MapConfig, engine_config: &EngineConfig) ->
 
     let scaledMountainousTerrain = Cache::new(scaledMountainousTerrain_mu);
 
-    let scaledHillyTerrain_sb0 = ScaleBias::new(hillyTerrain)
+    let scaledHillyTerrain_sb0 = ScaleBias::new(&hillyTerrain)
         .set_scale(0.0625)
         .set_bias(0.0625);
 
@@ -409,13 +499,13 @@ pub fn generate_texture(map_config: &MapConfig, engine_config: &EngineConfig) ->
 
     let scaledHillyTerrain = Cache::new(scaledHillyTerrain_mu);
 
-    let scaledPlainsTerrain_sb0 = ScaleBias::new(plainsTerrain)
+    let scaledPlainsTerrain_sb0 = ScaleBias::new(&plainsTerrain)
         .set_scale(0.00390625)
         .set_bias(0.0078125);
 
     let scaledPlainsTerrain = Cache::new(scaledPlainsTerrain_sb0);
 
-    let scaledBadlandsTerrain_sb = ScaleBias::new(badlandsTerrain)
+    let scaledBadlandsTerrain_sb = ScaleBias::new(&badlandsTerrain)
         .set_scale(0.0625)
         .set_bias(0.0625);
 
@@ -447,7 +537,7 @@ pub fn generate_texture(map_config: &MapConfig, engine_config: &EngineConfig) ->
         .set_scale(map_config.continent_height_scale)
         .set_bias(0.0);
 
-    let baseContinentElev_se = Select::new(baseContinentElev_sb, continentalShelf, &continentDef)
+    let baseContinentElev_se = Select::new(baseContinentElev_sb, &continentalShelf, &continentDef)
         .set_bounds(map_config.shelf_level - 1000.0, map_config.shelf_level)
         .set_falloff(0.03125);
 
@@ -484,7 +574,7 @@ pub fn generate_texture(map_config: &MapConfig, engine_config: &EngineConfig) ->
         Add::new(continentsWithMountains_ad0, continentsWithMountains_cu);
 
     let continentsWithMountains_se = Select::new(
-        continentsWithHills,
+        &continentsWithHills,
         continentsWithMountains_ad1,
         &terrainTypeDef,
     )
@@ -519,7 +609,7 @@ pub fn generate_texture(map_config: &MapConfig, engine_config: &EngineConfig) ->
 
     let continentsWithBadlands = Cache::new(continentsWithBadlands_ma);
 
-    let continentsWithRivers_sb = ScaleBias::new(riverPositions)
+    let continentsWithRivers_sb = ScaleBias::new(&riverPositions)
         .set_scale(map_config.river_depth / 2.0)
         .set_bias(-map_config.river_depth / 2.0);
 
@@ -540,20 +630,65 @@ pub fn generate_texture(map_config: &MapConfig, engine_config: &EngineConfig) ->
 
     let unscaledFinalPlanet = Cache::new(continentsWithRivers);
 
-    let noise_map = PlaneMapBuilder::new(&unscaledFinalPlanet)
+    let noise_map = match layer {
+        GenerationLayer::ContinentDef => build_layer_map(&continentDef, engine_config),
+        GenerationLayer::TerrainTypeDef => build_layer_map(&terrainTypeDef, engine_config),
+        GenerationLayer::MountainBaseDef => build_layer_map(&mountainBaseDef, engine_config),
+        GenerationLayer::MountainousHigh => build_layer_map(&mountainousHigh, engine_config),
+        GenerationLayer::MountainousLow => build_layer_map(&mountainousLow, engine_config),
+        GenerationLayer::MountainousTerrain => build_layer_map(&mountainousTerrain, engine_config),
+        GenerationLayer::HillyTerrain => build_layer_map(&hillyTerrain, engine_config),
+        GenerationLayer::PlainsTerrain => build_layer_map(&plainsTerrain, engine_config),
+        GenerationLayer::BadlandsSand => build_layer_map(&badlandsSand, engine_config),
+        GenerationLayer::BadlandsCliffs => build_layer_map(&badlandsCliffs, engine_config),
+        GenerationLayer::BadlandsTerrain => build_layer_map(&badlandsTerrain, engine_config),
+        GenerationLayer::RiverPositions => build_layer_map(&riverPositions, engine_config),
+        GenerationLayer::ContinentalShelf => build_layer_map(&continentalShelf, engine_config),
+        GenerationLayer::BaseContinentElev => build_layer_map(&baseContinentElev, engine_config),
+        GenerationLayer::ContinentsWithPlains => {
+            build_layer_map(&continentsWithPlains, engine_config)
+        }
+        GenerationLayer::ContinentsWithHills => {
+            build_layer_map(&continentsWithHills, engine_config)
+        }
+        GenerationLayer::ContinentsWithMountains => {
+            build_layer_map(&continentsWithMountains, engine_config)
+        }
+        GenerationLayer::ContinentsWithBadlands => {
+            build_layer_map(&continentsWithBadlands, engine_config)
+        }
+        GenerationLayer::Final => build_layer_map(&unscaledFinalPlanet, engine_config),
+    };
+
+    return noise_map;
+}
+
+fn build_layer_map(module: impl NoiseFn<f64, 3>, engine_config: &EngineConfig) -> NoiseMap {
+    return PlaneMapBuilder::new(module)
         .set_size(engine_config.world_size, engine_config.world_size)
         .set_x_bounds(-2.0, 2.0)
         .set_y_bounds(-2.0, 2.0)
         .build();
+}
 
-    write_image_to_file(
-        &ImageRenderer::new()
-            .set_gradient(ColorGradient::new().build_terrain_gradient())
-            .render(&noise_map),
-        "fbm_perlin.png",
-    );
+// Intermediate layers are shown in grayscale since most of them are not elevations
+pub fn render_layer(layer: GenerationLayer, map: &NoiseMap) -> NoiseImage {
+    let gradient = match layer {
+        GenerationLayer::Final => ColorGradient::new().build_terrain_gradient(),
+        _ => ColorGradient::new().build_grayscale_gradient(),
+    };
+    return ImageRenderer::new().set_gradient(gradient).render(map);
+}
 
-    return noise_map;
+// Samples every layer and writes it to the saves folder, for tuning the generator
+pub fn dump_layers(map_config: &MapConfig, engine_config: &EngineConfig) {
+    for layer in GenerationLayer::ALL {
+        let map = generate_layer(map_config, engine_config, layer);
+        write_image_to_file(
+            &render_layer(layer, &map),
+            &format!("layers/{}.png", layer.name()),
+        );
+    }
 }
 
 fn write_image_to_file(_image: &NoiseImage, filename: &str) {
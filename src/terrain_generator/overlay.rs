@@ -0,0 +1,233 @@
+use bevy::{
+    pbr::ExtendedMaterial,
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        texture::ImageSampler,
+    },
+};
+
+use crate::config_parser::EngineConfig;
+use crate::controls::{ActionState, InputAction};
+use crate::loading_screen::AppState;
+use crate::terrain_generator::TerrainMaterial;
+
+// How strongly province colors tint the terrain under them
+const PROVINCE_ALPHA: f32 = 0.6;
+
+pub struct MapOverlayPlugin;
+
+impl Plugin for MapOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::GeneratingMeshes), setup_map_overlay)
+            .add_systems(
+                Update,
+                (switch_overlay_mode, apply_overlay_to_materials)
+                    .chain()
+                    .run_if(in_state(AppState::InGame)),
+            );
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverlayMode {
+    #[default]
+    None,
+    Contours,
+    Grid,
+    Political,
+}
+
+impl OverlayMode {
+    // Matches the `overlay_mode` switch in terrain.wgsl
+    fn shader_index(&self) -> u32 {
+        match self {
+            OverlayMode::None => 0,
+            OverlayMode::Contours => 1,
+            OverlayMode::Grid => 2,
+            OverlayMode::Political => 3,
+        }
+    }
+}
+
+// The data texture has one texel per heightmap cell. Gameplay code paints province or ownership
+// colors into it, with the alpha channel controlling how strongly each cell is tinted. The
+// contour interval comes from the engine config when the material is made.
+#[derive(Resource)]
+pub struct MapOverlay {
+    pub mode: OverlayMode,
+    pub data_texture: Handle<Image>,
+}
+
+impl MapOverlay {
+    pub fn apply(&self, material: &mut TerrainMaterial) {
+        material.overlay_mode = self.mode.shader_index();
+        material.overlay_texture = Some(self.data_texture.clone());
+    }
+
+    // Colors a heightmap cell by the province that owns it, or clears it with None. Each id
+    // gets its own hue, so neighbouring provinces stand apart. Cells off the map are ignored.
+    pub fn set_province(
+        &self,
+        images: &mut Assets<Image>,
+        cell: (usize, usize),
+        province: Option<u32>,
+    ) {
+        let Some(image) = images.get_mut(&self.data_texture) else {
+            return;
+        };
+        let size = image.texture_descriptor.size;
+        let (x, z) = cell;
+        if x >= size.width as usize || z >= size.height as usize {
+            return;
+        }
+        let texel = match province {
+            Some(id) => province_color(id).to_srgba().to_u8_array(),
+            None => [0, 0, 0, 0],
+        };
+        let offset = (z * size.width as usize + x) * 4;
+        image.data[offset..offset + 4].copy_from_slice(&texel);
+    }
+}
+
+fn province_color(id: u32) -> Color {
+    // Steps round the color wheel by the golden angle so consecutive ids never look alike
+    let hue = (id as f32 * 137.508) % 360.0;
+    return Color::hsla(hue, 0.65, 0.5, PROVINCE_ALPHA);
+}
+
+fn setup_map_overlay(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    engine_config: Res<EngineConfig>,
+) {
+    let cells = (engine_config.world_size - 1) as u32;
+    let mut data_texture = Image::new_fill(
+        Extent3d {
+            width: cells,
+            height: cells,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 0],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD,
+    );
+    data_texture.sampler = ImageSampler::nearest();
+    commands.insert_resource(MapOverlay {
+        mode: OverlayMode::None,
+        data_texture: images.add(data_texture),
+    });
+}
+
+fn switch_overlay_mode(actions: Res<ActionState>, mut overlay: ResMut<MapOverlay>) {
+    let selected = if actions.just_pressed(InputAction::ToggleContourOverlay) {
+        OverlayMode::Contours
+    } else if actions.just_pressed(InputAction::ToggleGridOverlay) {
+        OverlayMode::Grid
+    } else if actions.just_pressed(InputAction::TogglePoliticalOverlay) {
+        OverlayMode::Political
+    } else {
+        return;
+    };
+    overlay.mode = if overlay.mode == selected {
+        OverlayMode::None
+    } else {
+        selected
+    };
+}
+
+fn apply_overlay_to_materials(
+    overlay: Res<MapOverlay>,
+    mut materials: ResMut<Assets<ExtendedMaterial<StandardMaterial, TerrainMaterial>>>,
+) {
+    if !overlay.is_changed() {
+        return;
+    }
+    for (_, material) in materials.iter_mut() {
+        overlay.apply(&mut material.extension);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::input::{
+        keyboard::{Key, KeyboardInput, NativeKey},
+        ButtonState, InputPlugin, InputSystem,
+    };
+
+    use super::*;
+    use crate::controls::{update_action_state, InputBindings};
+
+    #[test]
+    fn set_province_writes_the_cell_texel() {
+        let mut images = Assets::<Image>::default();
+        let data_texture = images.add(Image::new_fill(
+            Extent3d {
+                width: 4,
+                height: 3,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &[0, 0, 0, 0],
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::MAIN_WORLD,
+        ));
+        let overlay = MapOverlay {
+            mode: OverlayMode::Political,
+            data_texture: data_texture.clone(),
+        };
+
+        overlay.set_province(&mut images, (2, 1), Some(7));
+        overlay.set_province(&mut images, (4, 0), Some(7));
+        let data = &images.get(&data_texture).unwrap().data;
+        // Row 1 of a 4 texel wide texture, column 2
+        let offset = (4 + 2) * 4;
+        assert_eq!(
+            data[offset..offset + 4],
+            province_color(7).to_srgba().to_u8_array()
+        );
+        assert_ne!(province_color(7), province_color(8));
+        assert_eq!(data.iter().filter(|byte| **byte != 0).count(), 4);
+
+        overlay.set_province(&mut images, (2, 1), None);
+        assert!(images
+            .get(&data_texture)
+            .unwrap()
+            .data
+            .iter()
+            .all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn overlay_actions_switch_the_mode() {
+        let mut app = App::new();
+        app.add_plugins(InputPlugin)
+            .insert_resource(InputBindings::default())
+            .init_resource::<ActionState>()
+            .insert_resource(MapOverlay {
+                mode: OverlayMode::None,
+                data_texture: Handle::default(),
+            })
+            .add_systems(PreUpdate, update_action_state.after(InputSystem))
+            .add_systems(Update, switch_overlay_mode);
+        let mut tap = |key: KeyCode| -> OverlayMode {
+            for state in [ButtonState::Pressed, ButtonState::Released] {
+                app.world_mut().send_event(KeyboardInput {
+                    key_code: key,
+                    logical_key: Key::Unidentified(NativeKey::Unidentified),
+                    state,
+                    window: Entity::PLACEHOLDER,
+                });
+                app.update();
+            }
+            return app.world().resource::<MapOverlay>().mode;
+        };
+
+        assert_eq!(tap(KeyCode::F7), OverlayMode::Grid);
+        assert_eq!(tap(KeyCode::F6), OverlayMode::Contours);
+        assert_eq!(tap(KeyCode::F8), OverlayMode::Political);
+        assert_eq!(tap(KeyCode::F8), OverlayMode::None);
+    }
+}
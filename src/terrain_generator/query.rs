@@ -0,0 +1,100 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::config_parser::{EngineConfig, MapConfig};
+use crate::terrain_generator::chunk::world_to_grid;
+use crate::terrain_generator::ChunkCoord;
+use crate::terrain_generator::TerrainMap;
+
+impl TerrainMap {
+    // Samples the plane of the mesh triangle under (x, z), so the result matches the rendered
+    // surface exactly. Each cell is split along its top-left to bottom-right diagonal.
+    pub fn height_at(&self, engine_config: &EngineConfig, x: f32, z: f32) -> Option<f32> {
+        let (h00, dh_dx, dh_dz, fx, fz) = self.triangle_at(engine_config, x, z)?;
+        return Some(h00 + fx * dh_dx + fz * dh_dz);
+    }
+
+    pub fn normal_at(&self, engine_config: &EngineConfig, x: f32, z: f32) -> Option<Vec3> {
+        let (_, dh_dx, dh_dz, _, _) = self.triangle_at(engine_config, x, z)?;
+        return Some(Vec3::new(-dh_dx, 1.0, -dh_dz).normalize());
+    }
+
+    // Returns the height of the cell's top-left corner, the triangle's gradient along x and z,
+    // and the position of (x, z) inside the cell.
+    fn triangle_at(
+        &self,
+        engine_config: &EngineConfig,
+        x: f32,
+        z: f32,
+    ) -> Option<(f32, f32, f32, f32, f32)> {
+        let (grid_x, grid_z) = world_to_grid(Vec3::new(x, 0.0, z), engine_config)?;
+        let x0 = grid_x.min(engine_config.world_size - 2);
+        let z0 = grid_z.min(engine_config.world_size - 2);
+        let fx = x - x0 as f32;
+        let fz = z - z0 as f32;
+
+        let height =
+            |x: usize, z: usize| self.map.get_value(x, z) as f32 * engine_config.world_height;
+        let h00 = height(x0, z0);
+        let h10 = height(x0 + 1, z0);
+        let h01 = height(x0, z0 + 1);
+        let h11 = height(x0 + 1, z0 + 1);
+
+        if fz >= fx {
+            // Top-left, bottom-left, bottom-right
+            return Some((h00, h11 - h01, h01 - h00, fx, fz));
+        }
+        // Top-left, bottom-right, top-right
+        return Some((h00, h10 - h00, h11 - h10, fx, fz));
+    }
+}
+
+#[derive(SystemParam)]
+pub struct TerrainQuery<'w> {
+    terrain_map: Res<'w, TerrainMap>,
+    engine_config: Res<'w, EngineConfig>,
+    map_config: Res<'w, MapConfig>,
+}
+
+impl<'w> TerrainQuery<'w> {
+    pub fn height_at(&self, x: f32, z: f32) -> Option<f32> {
+        return self.terrain_map.height_at(&self.engine_config, x, z);
+    }
+
+    pub fn normal_at(&self, x: f32, z: f32) -> Option<Vec3> {
+        return self.terrain_map.normal_at(&self.engine_config, x, z);
+    }
+
+    // Angle between the surface and the horizontal, in radians.
+    pub fn slope_at(&self, x: f32, z: f32) -> Option<f32> {
+        let normal = self.normal_at(x, z)?;
+        return Some(normal.dot(Vec3::Y).clamp(-1.0, 1.0).acos());
+    }
+
+    // Heightmap cell containing the world position, None off the map. Positions on the far
+    // edges belong to the last cell, as there is no cell beyond them.
+    pub fn cell_at(&self, position: Vec3) -> Option<(usize, usize)> {
+        let (x, z) = world_to_grid(position, &self.engine_config)?;
+        let last = self.engine_config.world_size - 2;
+        return Some((x.min(last), z.min(last)));
+    }
+
+    pub fn chunk_at(&self, position: Vec3) -> Option<ChunkCoord> {
+        return ChunkCoord::from_world(position, &self.engine_config);
+    }
+
+    pub fn sea_level(&self) -> f32 {
+        return self.map_config.sea_level as f32 * self.engine_config.world_height;
+    }
+
+    pub fn is_underwater(&self, x: f32, z: f32) -> bool {
+        match self.height_at(x, z) {
+            Some(height) => height < self.sea_level(),
+            None => false,
+        }
+    }
+
+    pub fn water_depth_at(&self, x: f32, z: f32) -> Option<f32> {
+        let height = self.height_at(x, z)?;
+        return Some((self.sea_level() - height).max(0.0));
+    }
+}